@@ -0,0 +1,193 @@
+// times the chaining map against std's HashMap with long String keys, where hashing and
+// comparing keys dominates. run with `cargo bench`
+//
+// to show what caching each key's hash buys, the same workloads also run on Uncached below,
+// which is the chaining map as it was before the hashes were stored: it hashes every key again
+// when it rehashes and calls K::eq on every pair it walks past in a bucket
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use hashmap::GrowthPolicy;
+
+const KEYS: usize = 200_000;
+const KEY_LEN: usize = 64;
+// keys that all land in one bucket, each lookup walks half the chain on average
+const COLLIDING: usize = 2_000;
+
+// keys share a long prefix so comparing two of them has to look at most of their bytes
+fn long_keys(n: usize) -> Vec<String> {
+    (0..n)
+        .map(|i| format!("{:0>width$}", i, width = KEY_LEN))
+        .collect()
+}
+
+fn time(name: &str, per: usize, f: impl FnOnce()) {
+    let start = Instant::now();
+    f();
+    let elapsed: Duration = start.elapsed();
+    println!(
+        "{name:<36} {:>10.1} ns/key",
+        elapsed.as_nanos() as f64 / per as f64
+    );
+}
+
+// the chaining map without cached hashes: same table, load factor and growth as HashMap
+struct Uncached<K, V> {
+    buckets: Vec<Vec<(K, V)>>,
+    items: usize,
+    max_load: f64,
+    hash_builder: RandomState,
+}
+
+impl<K: Hash + Eq, V> Uncached<K, V> {
+    fn new() -> Self {
+        Uncached::with_buckets(10, 0.75)
+    }
+
+    fn with_buckets(nbuckets: usize, max_load: f64) -> Self {
+        Uncached {
+            buckets: (0..nbuckets).map(|_| Vec::new()).collect(),
+            items: 0,
+            max_load,
+            hash_builder: RandomState::new(),
+        }
+    }
+
+    fn bucket<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        (self.hash_builder.hash_one(key) % self.buckets.len() as u64) as usize
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if (self.items + 1) as f64 > self.buckets.len() as f64 * self.max_load {
+            self.rehash(self.buckets.len() * 2);
+        }
+        let bucket = self.bucket(&key);
+        match self.buckets[bucket].iter_mut().find(|(k, _)| *k == key) {
+            Some(pair) => pair.1 = value,
+            None => {
+                self.buckets[bucket].push((key, value));
+                self.items += 1;
+            }
+        }
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.buckets[self.bucket(key)]
+            .iter()
+            .find(|(k, _)| k.borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    fn capacity(&self) -> usize {
+        (self.buckets.len() as f64 * self.max_load) as usize
+    }
+
+    // makes room for `additional` more items with one rehash, like HashMap::reserve
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.items + additional;
+        if needed > self.capacity() {
+            self.rehash((needed as f64 / self.max_load).ceil() as usize);
+        }
+    }
+
+    fn rehash(&mut self, nbuckets: usize) {
+        let old = std::mem::replace(
+            &mut self.buckets,
+            (0..nbuckets).map(|_| Vec::new()).collect(),
+        );
+        for (key, value) in old.into_iter().flatten() {
+            let bucket = self.bucket(&key);
+            self.buckets[bucket].push((key, value));
+        }
+    }
+}
+
+fn main() {
+    let keys = long_keys(KEYS);
+    let misses: Vec<String> = keys.iter().map(|key| format!("{key}!")).collect();
+
+    let mut map = hashmap::HashMap::new();
+    time("HashMap insert (with growth)", KEYS, || {
+        for key in &keys {
+            map.insert(key.clone(), ());
+        }
+    });
+    time("HashMap get hit", KEYS, || {
+        for key in &keys {
+            black_box(map.get(key.as_str()));
+        }
+    });
+    time("HashMap get miss", KEYS, || {
+        for key in &misses {
+            black_box(map.get(key.as_str()));
+        }
+    });
+    time("HashMap reserve (one rehash)", KEYS, || {
+        map.reserve(map.capacity());
+    });
+
+    let mut uncached = Uncached::new();
+    time("uncached insert (with growth)", KEYS, || {
+        for key in &keys {
+            uncached.insert(key.clone(), ());
+        }
+    });
+    time("uncached get hit", KEYS, || {
+        for key in &keys {
+            black_box(uncached.get(key.as_str()));
+        }
+    });
+    time("uncached get miss", KEYS, || {
+        for key in &misses {
+            black_box(uncached.get(key.as_str()));
+        }
+    });
+    time("uncached reserve (one rehash)", KEYS, || {
+        uncached.reserve(uncached.capacity());
+    });
+
+    let mut std_map = std::collections::HashMap::new();
+    time("std insert (with growth)", KEYS, || {
+        for key in &keys {
+            std_map.insert(key.clone(), ());
+        }
+    });
+    time("std get hit", KEYS, || {
+        for key in &keys {
+            black_box(std_map.get(key.as_str()));
+        }
+    });
+    time("std get miss", KEYS, || {
+        for key in &misses {
+            black_box(std_map.get(key.as_str()));
+        }
+    });
+
+    // one bucket holding every key: a cached hash rules out a pair with one u64 comparison,
+    // without it every pair walked past costs a string comparison
+    let colliding = long_keys(COLLIDING);
+    let policy = GrowthPolicy::new()
+        .initial_buckets(1)
+        .max_load(COLLIDING as f64 * 2.0);
+    let mut map = hashmap::HashMap::with_policy(policy);
+    let mut uncached = Uncached::with_buckets(1, COLLIDING as f64 * 2.0);
+    for key in &colliding {
+        map.insert(key.clone(), ());
+        uncached.insert(key.clone(), ());
+    }
+    time("HashMap one-bucket chain scan", COLLIDING, || {
+        for key in &colliding {
+            black_box(map.get(key.as_str()));
+        }
+    });
+    time("uncached one-bucket chain scan", COLLIDING, || {
+        for key in &colliding {
+            black_box(uncached.get(key.as_str()));
+        }
+    });
+}
@@ -0,0 +1,214 @@
+use alloc::alloc::handle_alloc_error;
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::Allocator;
+
+// the bits of Vec that HashMap needs, with its memory coming from an Allocator. used for the
+// bucket table and for every bucket in it. each one keeps its own handle to the allocator, which
+// costs nothing for Global and a pointer per bucket for a borrowed arena
+pub(crate) struct AllocVec<T, A: Allocator> {
+    ptr: NonNull<T>,
+    cap: usize,
+    len: usize,
+    alloc: A,
+    marker: PhantomData<T>,
+}
+
+// SAFETY: AllocVec owns its elements like Vec does, so it is Send and Sync when they are
+unsafe impl<T: Send, A: Allocator + Send> Send for AllocVec<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for AllocVec<T, A> {}
+
+impl<T, A: Allocator> AllocVec<T, A> {
+    // doesn't allocate until the first push
+    pub(crate) fn new_in(alloc: A) -> Self {
+        AllocVec {
+            ptr: NonNull::dangling(),
+            // zero sized elements never need memory
+            cap: if mem::size_of::<T>() == 0 {
+                usize::MAX
+            } else {
+                0
+            },
+            len: 0,
+            alloc,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        let mut vec = AllocVec::new_in(alloc);
+        if capacity > vec.cap {
+            vec.grow_to(capacity);
+        }
+        vec
+    }
+
+    pub(crate) fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    // the start of the buffer, for handing out pointers to several elements at once without
+    // going through a &mut [T] that would cover (and invalidate) all of them
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    fn layout(cap: usize) -> Layout {
+        Layout::array::<T>(cap).expect("capacity overflow")
+    }
+
+    fn grow_to(&mut self, cap: usize) {
+        let new_layout = Self::layout(cap);
+        let new = if self.cap == 0 {
+            self.alloc.allocate(new_layout)
+        } else {
+            // SAFETY: ptr was allocated by self.alloc with the layout for self.cap
+            unsafe {
+                self.alloc
+                    .grow(self.ptr.cast(), Self::layout(self.cap), new_layout)
+            }
+        };
+        let new = new.unwrap_or_else(|_| handle_alloc_error(new_layout));
+        self.ptr = new.cast();
+        self.cap = cap;
+    }
+
+    pub(crate) fn push(&mut self, value: T) {
+        if self.len == self.cap {
+            // same small first allocation and doubling as Vec
+            let cap = self.cap.checked_mul(2).expect("capacity overflow").max(4);
+            self.grow_to(cap);
+        }
+        // SAFETY: len < cap, so the slot is inside the allocation and unused
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: the element at the old last index is initialised and no longer counted
+        Some(unsafe { self.ptr.as_ptr().add(self.len).read() })
+    }
+
+    // removes the element at `index` and moves the last one into its place
+    pub(crate) fn swap_remove(&mut self, index: usize) -> T {
+        let last = self.len - 1;
+        self.swap(index, last);
+        self.pop().unwrap()
+    }
+
+    // removes the element at `index` and shifts the ones after it down, keeping their order
+    pub(crate) fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "removal index out of bounds");
+        // SAFETY: index is in bounds, the tail is moved over the read element and the length
+        // drops by one so nothing is read or dropped twice
+        unsafe {
+            let at = self.ptr.as_ptr().add(index);
+            let value = at.read();
+            ptr::copy(at.add(1), at, self.len - index - 1);
+            self.len -= 1;
+            value
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        let elements: *mut [T] = &mut **self;
+        // the length goes first, so a panicking destructor leaks the rest instead of dropping
+        // them twice
+        self.len = 0;
+        // SAFETY: the elements were initialised and are no longer counted
+        unsafe { ptr::drop_in_place(elements) };
+    }
+}
+
+impl<T, A: Allocator> Deref for AllocVec<T, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the first len elements are initialised, ptr is dangling but aligned when len is 0
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T, A: Allocator> DerefMut for AllocVec<T, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: as for deref, and self is borrowed mutably
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for AllocVec<T, A> {
+    fn clone(&self) -> Self {
+        let mut vec = AllocVec::with_capacity_in(self.len, self.alloc.clone());
+        for value in self.iter() {
+            vec.push(value.clone());
+        }
+        vec
+    }
+}
+
+impl<T, A: Allocator> Drop for AllocVec<T, A> {
+    fn drop(&mut self) {
+        self.clear();
+        if self.cap != 0 && mem::size_of::<T>() != 0 {
+            // SAFETY: ptr was allocated by self.alloc with the layout for self.cap
+            unsafe {
+                self.alloc
+                    .deallocate(self.ptr.cast(), Self::layout(self.cap))
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Global;
+    use std::rc::Rc;
+
+    #[test]
+    fn push_pop_and_remove() {
+        let mut vec = AllocVec::new_in(Global);
+        for i in 0..100 {
+            vec.push(i);
+        }
+        assert_eq!(vec.len(), 100);
+        assert_eq!(vec.remove(10), 10);
+        assert_eq!(vec.swap_remove(0), 0);
+        assert_eq!(vec[0], 99);
+        assert_eq!(vec[10], 11);
+        assert_eq!(vec.pop(), Some(98));
+        assert_eq!(vec.len(), 97);
+
+        let copy = vec.clone();
+        vec.clear();
+        assert!(vec.is_empty());
+        assert_eq!(copy.len(), 97);
+    }
+
+    #[test]
+    fn drops_every_element_once() {
+        let value = Rc::new(());
+        let mut vec = AllocVec::new_in(Global);
+        for _ in 0..10 {
+            vec.push(Rc::clone(&value));
+        }
+        drop(vec.remove(3));
+        drop(vec.swap_remove(0));
+        assert_eq!(Rc::strong_count(&value), 9);
+        drop(vec);
+        assert_eq!(Rc::strong_count(&value), 1);
+
+        let mut units = AllocVec::new_in(Global);
+        units.push(());
+        assert_eq!(units.pop(), Some(()));
+    }
+}
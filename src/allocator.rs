@@ -0,0 +1,217 @@
+use alloc::alloc::{alloc, dealloc, realloc};
+use core::alloc::Layout;
+use core::fmt;
+use core::ptr::{self, NonNull};
+
+/// Where a HashMap gets the memory for its bucket table and its buckets. std's own Allocator
+/// trait is still unstable, so this is a smaller stand-in that works on stable:
+///
+/// ```text
+/// let arena = MyBumpArena::new();
+/// let mut map = HashMap::new_in(&arena);
+/// ```
+///
+/// Implemented for `&A` as well, so an arena can be shared by reference between many maps.
+///
+/// # Safety
+///
+/// Implementors must hand out memory that fits the layout and stays valid until it is passed
+/// back to `deallocate` or `grow`, on this allocator or on a clone of it.
+pub unsafe trait Allocator {
+    /// Allocates a block for `layout`, which never has a size of zero.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// Frees a block handed out by `allocate` or `grow`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from this allocator (or a clone of it) with this exact layout, and must
+    /// not be used afterwards.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Moves an allocation into a bigger one with the same alignment. The default allocates,
+    /// copies and frees; allocators that can extend in place should override it.
+    ///
+    /// # Safety
+    ///
+    /// Same as `deallocate` for `ptr` and `old_layout`, and `new_layout` must be at least as big
+    /// with the same alignment. On success `ptr` must not be used again; on failure it is still
+    /// valid and owned by the caller.
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let new = self.allocate(new_layout)?;
+        // SAFETY: both blocks are at least old_layout.size() long and can't overlap
+        unsafe {
+            ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr(), old_layout.size());
+            self.deallocate(ptr, old_layout);
+        }
+        Ok(new)
+    }
+}
+
+// the allocator couldn't satisfy a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+// the global allocator, what maps use unless they're built with one of the _in constructors
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        // SAFETY: the trait promises a non-zero size
+        NonNull::new(unsafe { alloc(layout) }).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFETY: the caller promises ptr came from allocate with this layout
+        unsafe { dealloc(ptr.as_ptr(), layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        debug_assert_eq!(old_layout.align(), new_layout.align());
+        // SAFETY: the caller promises ptr came from allocate with old_layout, and realloc keeps
+        // the alignment
+        NonNull::new(unsafe { realloc(ptr.as_ptr(), old_layout, new_layout.size()) })
+            .ok_or(AllocError)
+    }
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFETY: forwarded as is
+        unsafe { (**self).deallocate(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        // SAFETY: forwarded as is
+        unsafe { (**self).grow(ptr, old_layout, new_layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashMap;
+    use std::cell::{Cell, UnsafeCell};
+
+    // forwards to Global and counts what goes through it
+    #[derive(Default)]
+    struct Tracking {
+        allocations: Cell<usize>,
+        frees: Cell<usize>,
+        live_bytes: Cell<usize>,
+    }
+
+    unsafe impl Allocator for Tracking {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            self.allocations.set(self.allocations.get() + 1);
+            self.live_bytes.set(self.live_bytes.get() + layout.size());
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.frees.set(self.frees.get() + 1);
+            self.live_bytes.set(self.live_bytes.get() - layout.size());
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    #[test]
+    fn every_allocation_goes_through_the_allocator() {
+        let tracking = Tracking::default();
+        {
+            let mut map = HashMap::new_in(&tracking);
+            assert_eq!(tracking.allocations.get(), 0);
+
+            for i in 0..1000 {
+                map.insert(i, i);
+            }
+            map.reserve(5000);
+            assert!(tracking.live_bytes.get() > 0);
+
+            let copy = map.clone();
+            assert_eq!(copy, map);
+            for i in 0..500 {
+                map.remove(&i);
+            }
+            map.shrink_to_fit();
+            assert_eq!(map.drain().take(10).count(), 10);
+            assert_eq!(copy.into_iter().take(10).count(), 10);
+        }
+        assert!(tracking.allocations.get() > 1000 / 4);
+        assert_eq!(tracking.frees.get(), tracking.allocations.get());
+        assert_eq!(tracking.live_bytes.get(), 0);
+    }
+
+    #[test]
+    fn with_capacity_in_allocates_only_the_table() {
+        let tracking = Tracking::default();
+        let map: HashMap<u32, u32, _, _> = HashMap::with_capacity_in(1000, &tracking);
+        // one allocation for the table, the buckets stay empty until something lands in them
+        assert_eq!(tracking.allocations.get(), 1);
+        assert!(map.capacity() >= 1000);
+        assert!(core::ptr::eq(*map.allocator(), &tracking));
+    }
+
+    // hands out slices of a fixed buffer and never frees, like a request scoped arena
+    struct Bump {
+        buffer: UnsafeCell<[u8; 1 << 16]>,
+        used: Cell<usize>,
+    }
+
+    unsafe impl Allocator for Bump {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            let base = self.buffer.get() as usize;
+            let start = (base + self.used.get()).next_multiple_of(layout.align()) - base;
+            let end = start.checked_add(layout.size()).ok_or(AllocError)?;
+            if end > 1 << 16 {
+                return Err(AllocError);
+            }
+            self.used.set(end);
+            NonNull::new(self.buffer.get().cast::<u8>().wrapping_add(start)).ok_or(AllocError)
+        }
+
+        unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {}
+    }
+
+    #[test]
+    fn bump_arena() {
+        let arena = Bump {
+            buffer: UnsafeCell::new([0; 1 << 16]),
+            used: Cell::new(0),
+        };
+        let mut map = HashMap::with_hasher_in(crate::FixedState::with_seed(1), &arena);
+        for i in 0..200u64 {
+            map.insert(i, i * i);
+        }
+        for i in 0..200u64 {
+            assert_eq!(map.get(&i), Some(&(i * i)));
+        }
+        assert!(arena.used.get() > 200 * 24);
+    }
+}
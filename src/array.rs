@@ -0,0 +1,252 @@
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::{FixedState, Slot};
+
+// a map that never allocates: N slots in a fixed array, filled by linear probing. every slot can
+// be used, so it holds exactly N pairs and insert hands the pair back once it is full instead of
+// growing. removals shift the pairs after the hole back towards their home slot, so a table that
+// can never rehash doesn't fill up with tombstones.
+//
+// new is a const fn so a map can live in a static:
+//
+//     static ROUTES: Mutex<ArrayHashMap<u32, Handler, 64>> = Mutex::new(ArrayHashMap::new());
+//
+// the default FixedState has a fixed seed, since there is no RandomState in a const context.
+// maps that hold untrusted keys should use with_hasher(FixedState::with_seed(..))
+pub struct ArrayHashMap<K, V, const N: usize, S = FixedState> {
+    slots: [Option<Slot<K, V>>; N],
+    items: usize,
+    hash_builder: S,
+}
+
+impl<K, V, const N: usize> ArrayHashMap<K, V, N, FixedState> {
+    pub const fn new() -> Self {
+        ArrayHashMap::with_hasher(FixedState::new())
+    }
+}
+
+impl<K, V, const N: usize, S> ArrayHashMap<K, V, N, S> {
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        ArrayHashMap {
+            slots: [const { None }; N],
+            items: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    // always N, the table never grows
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items == N
+    }
+
+    // iterates over the pairs in slot order, which is arbitrary
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|slot| (&slot.key, &slot.value))
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot = None;
+        }
+        self.items = 0;
+    }
+
+    // the slot a hash probes from
+    fn home(hash: u64) -> usize {
+        (hash % N as u64) as usize
+    }
+
+    // how many steps it takes to probe from `from` to `to`, wrapping around the end
+    fn distance(from: usize, to: usize) -> usize {
+        (to + N - from) % N
+    }
+}
+
+impl<K, V, const N: usize, S: Default> Default for ArrayHashMap<K, V, N, S> {
+    fn default() -> Self {
+        ArrayHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, const N: usize, S> ArrayHashMap<K, V, N, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // walks the probe sequence of `hash` and returns the slot holding the key or the first empty
+    // slot, whichever comes first. None means the table is full and the key isn't in it
+    fn probe<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if N == 0 {
+            return None;
+        }
+        let home = Self::home(hash);
+        (0..N)
+            .map(|step| (home + step) % N)
+            .find(|&index| match &self.slots[index] {
+                Some(slot) => slot.hash == hash && slot.key.borrow() == key,
+                None => true,
+            })
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.items == 0 {
+            return None;
+        }
+        let index = self.probe(self.hash_builder.hash_one(key), key)?;
+        self.slots[index].is_some().then_some(index)
+    }
+
+    // inserts the pair, returning the old value if the key was already there. a new key in a
+    // full map gets the pair back as the error
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        let hash = self.hash_builder.hash_one(&key);
+        let Some(index) = self.probe(hash, &key) else {
+            return Err((key, value));
+        };
+        match &mut self.slots[index] {
+            Some(slot) => Ok(Some(mem::replace(&mut slot.value, value))),
+            empty => {
+                *empty = Some(Slot { hash, key, value });
+                self.items += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.slots[index].as_ref().map(|slot| &slot.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.slots[index].as_mut().map(|slot| &mut slot.value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut hole = self.find(key)?;
+        let removed = self.slots[hole].take()?;
+        self.items -= 1;
+
+        // pull each following pair back into the hole if that doesn't put it before its home
+        // slot, until the run ends at an empty slot
+        let mut index = (hole + 1) % N;
+        while let Some(slot) = &self.slots[index] {
+            let home = Self::home(slot.hash);
+            if Self::distance(home, index) >= Self::distance(hole, index) {
+                self.slots[hole] = self.slots[index].take();
+                hole = index;
+            }
+            index = (index + 1) % N;
+        }
+        Some(removed.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap as StdHashMap;
+    use std::sync::Mutex;
+
+    #[test]
+    fn full_map_hands_the_pair_back() {
+        let mut map = ArrayHashMap::<u32, u32, 4>::new();
+        for i in 0..4 {
+            assert_eq!(map.insert(i, i), Ok(None));
+        }
+        assert!(map.is_full());
+        assert_eq!(map.insert(4, 4), Err((4, 4)));
+        assert_eq!(map.get(&4), None);
+
+        // existing keys can still be updated, and removing makes room again
+        assert_eq!(map.insert(2, 20), Ok(Some(2)));
+        assert_eq!(map.remove(&0), Some(0));
+        assert_eq!(map.insert(4, 4), Ok(None));
+        assert_eq!(map.len(), 4);
+
+        let mut none = ArrayHashMap::<u32, u32, 0>::new();
+        assert_eq!(none.insert(1, 1), Err((1, 1)));
+        assert_eq!(none.remove(&1), None);
+    }
+
+    #[test]
+    fn removals_keep_probe_runs_intact() {
+        // a small odd sized table makes long wrapping runs, checked against std after every step
+        let mut map = ArrayHashMap::<u32, u32, 13, _>::with_hasher(FixedState::with_seed(3));
+        let mut reference = StdHashMap::new();
+        for i in 0..2_000u32 {
+            let key = i.wrapping_mul(2_654_435_761) % 40;
+            if i % 3 == 0 {
+                assert_eq!(map.remove(&key), reference.remove(&key));
+            } else if !map.is_full() || map.contains_key(&key) {
+                assert_eq!(map.insert(key, i), Ok(reference.insert(key, i)));
+            }
+            assert_eq!(map.len(), reference.len());
+            for key in 0..40 {
+                assert_eq!(map.get(&key), reference.get(&key));
+            }
+        }
+        assert_eq!(map.iter().count(), reference.len());
+    }
+
+    static MAP: Mutex<ArrayHashMap<u32, &str, 16>> = Mutex::new(ArrayHashMap::new());
+
+    #[test]
+    fn lives_in_a_static() {
+        let mut map = MAP.lock().unwrap();
+        map.insert(7, "seven").unwrap();
+        assert_eq!(map.get(&7), Some(&"seven"));
+        map.clear();
+        assert!(map.is_empty());
+    }
+}
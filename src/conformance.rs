@@ -0,0 +1,52 @@
+// the basic map contract, checked the same way against every storage backend. each backend's own
+// module only tests what is particular to it
+
+// `$new` builds an empty map, one that hasn't allocated a table yet where the backend allows it.
+// `$inserted` turns what insert returns into the Option<V> of the previous value, for backends
+// whose insert can fail
+macro_rules! conformance {
+    ($name:ident, $new:expr) => {
+        conformance!($name, $new, |previous| previous);
+    };
+    ($name:ident, $new:expr, $inserted:expr) => {
+        mod $name {
+            use crate::*;
+
+            #[test]
+            fn insert_get_remove() {
+                let mut map = $new;
+                // a fresh map may not have a table yet, every lookup has to cope with that
+                assert_eq!(map.get("abc"), None);
+                assert_eq!(map.get_mut("abc"), None);
+                assert!(!map.contains_key("abc"));
+                assert_eq!(map.remove("abc"), None);
+                assert!(map.is_empty());
+
+                let inserted = $inserted;
+                assert_eq!(inserted(map.insert("abc", 1)), None);
+                assert_eq!(inserted(map.insert("abc", 2)), Some(1));
+                assert_eq!(map.get("abc"), Some(&2));
+                assert_eq!(map.get("def"), None);
+                assert!(map.contains_key("abc"));
+                assert_eq!(map.len(), 1);
+
+                *map.get_mut("abc").unwrap() += 1;
+                assert_eq!(map.remove("abc"), Some(3));
+                assert_eq!(map.remove("abc"), None);
+                assert_eq!(map.get("abc"), None);
+                assert!(map.is_empty());
+            }
+        }
+    };
+}
+
+conformance!(hash_map, HashMap::new());
+conformance!(open, OpenHashMap::new());
+conformance!(robin_hood, RobinHoodHashMap::new());
+conformance!(swiss, SwissHashMap::new());
+conformance!(incremental, IncrementalHashMap::new());
+conformance!(small, SmallHashMap::<_, _, 4>::new());
+conformance!(array, ArrayHashMap::<_, _, 8>::new(), |inserted: Result<_, (&str, i32)>| {
+    inserted.unwrap()
+});
+conformance!(linked, LinkedHashMap::new());
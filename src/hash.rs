@@ -0,0 +1,136 @@
+use core::hash::{BuildHasher, Hasher};
+
+// deterministic alternative to RandomState for when the bucket layout has to be reproducible
+// (tests, snapshots, replays). every FixedState with the same seed hashes keys the same way,
+// so only use it when the keys don't come from someone who could pick them to collide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedState {
+    k0: u64,
+    k1: u64,
+}
+
+impl FixedState {
+    // the all zero seed
+    pub const fn new() -> Self {
+        FixedState::with_seed(0)
+    }
+
+    pub const fn with_seed(seed: u64) -> Self {
+        FixedState::with_keys(seed, seed ^ 0x736f_6d65_7073_6575)
+    }
+
+    // both 64 bit halves of the SipHash key
+    pub const fn with_keys(k0: u64, k1: u64) -> Self {
+        FixedState { k0, k1 }
+    }
+}
+
+// same as new, it is what maps get when they are built without std
+impl Default for FixedState {
+    fn default() -> Self {
+        FixedState::new()
+    }
+}
+
+impl BuildHasher for FixedState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_keys(self.k0, self.k1)
+    }
+}
+
+// SipHash-1-3, the same function std's DefaultHasher currently uses, written out here so the
+// keys can be chosen by the caller
+#[derive(Clone, Debug)]
+pub struct SipHasher13 {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    // bytes written so far that don't yet fill a whole 8 byte word
+    tail: u64,
+    ntail: usize,
+    length: usize,
+}
+
+impl SipHasher13 {
+    pub const fn new_with_keys(k0: u64, k1: u64) -> Self {
+        SipHasher13 {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            ntail: 0,
+            length: 0,
+        }
+    }
+
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    fn compress(&mut self, word: u64) {
+        self.v3 ^= word;
+        self.round();
+        self.v0 ^= word;
+    }
+}
+
+impl Hasher for SipHasher13 {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.length += bytes.len();
+
+        // top up the partial word left over from the last write first
+        if self.ntail != 0 {
+            let fill = (8 - self.ntail).min(bytes.len());
+            for (i, &byte) in bytes[..fill].iter().enumerate() {
+                self.tail |= (byte as u64) << (8 * (self.ntail + i));
+            }
+            self.ntail += fill;
+            bytes = &bytes[fill..];
+            if self.ntail < 8 {
+                return;
+            }
+            let word = self.tail;
+            self.compress(word);
+            self.tail = 0;
+            self.ntail = 0;
+        }
+
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            self.compress(u64::from_le_bytes(word.try_into().unwrap()));
+        }
+
+        for (i, &byte) in words.remainder().iter().enumerate() {
+            self.tail |= (byte as u64) << (8 * i);
+        }
+        self.ntail = words.remainder().len();
+    }
+
+    fn finish(&self) -> u64 {
+        let mut state = self.clone();
+        let last = ((self.length as u64 & 0xff) << 56) | self.tail;
+        state.compress(last);
+        state.v2 ^= 0xff;
+        state.round();
+        state.round();
+        state.round();
+        state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+    }
+}
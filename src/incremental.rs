@@ -0,0 +1,340 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::DefaultHashBuilder;
+use crate::policy::fibonacci_index;
+
+const INITIAL_NBUCKETS: usize = 16;
+
+// number of old buckets moved over on every insert, get_mut or remove while a resize is running.
+// growth doubles the table at a 3/4 load, so the next resize is at least 3/4 * n inserts away and
+// moving 2 or more of the n old buckets per insert always finishes in time
+const MIGRATE_STEP: usize = 4;
+
+// same chaining layout and API as HashMap, but growing never rehashes everything at once.
+// when the load passes 3/4 the table doubles, and every insert, get_mut and remove moves a few
+// old buckets across until the old table is empty. get can't help, it only has a shared borrow.
+//
+// tables are powers of two and keys are placed by the top bits of their hash, so old bucket i
+// splits into new buckets 2i and 2i + 1. that keeps every step bounded at both ends of a resize:
+// the new table is only reserved when the resize starts and its buckets are pushed two at a time
+// as old buckets are split, and each old bucket is popped off the front of the old table as it
+// goes, so nothing is left to drop in bulk when the last one is done. it also means every key has
+// exactly one home: lookups check the old table for buckets not yet split and the new one after.
+pub struct IncrementalHashMap<K, V, S = DefaultHashBuilder> {
+    // the current table. while a resize runs only its first 2 * migrated buckets exist yet
+    buckets: Vec<Vec<(K, V)>>,
+    // size of the current table, whether or not all of its buckets exist yet
+    nbuckets: usize,
+    // the old buckets still to be split, starting with old bucket `migrated`. empty when no
+    // resize is running
+    old_buckets: VecDeque<Vec<(K, V)>>,
+    // old buckets below this index have already been moved
+    migrated: usize,
+    items: usize,
+    hash_builder: S,
+}
+
+impl<K, V> IncrementalHashMap<K, V, DefaultHashBuilder> {
+    pub fn new() -> Self {
+        IncrementalHashMap::with_hasher(DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> IncrementalHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        IncrementalHashMap {
+            buckets: Vec::new(),
+            nbuckets: 0,
+            old_buckets: VecDeque::new(),
+            migrated: 0,
+            items: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    // number of items the map can hold before the next resize starts
+    pub fn capacity(&self) -> usize {
+        self.nbuckets * 3 / 4
+    }
+
+    // true while pairs are still being moved out of the previous table
+    pub fn is_migrating(&self) -> bool {
+        !self.old_buckets.is_empty()
+    }
+
+    // iterates over the pairs of both tables, in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.old_buckets
+            .iter()
+            .chain(&self.buckets)
+            .flatten()
+            .map(|(key, value)| (key, value))
+    }
+
+    // the table and bucket a hash lives in: the old table if its old bucket hasn't been split yet,
+    // the current one otherwise
+    fn locate(&self, hash: u64) -> (bool, usize) {
+        if self.is_migrating() {
+            let old = fibonacci_index(hash, self.nbuckets / 2);
+            if old >= self.migrated {
+                return (true, old - self.migrated);
+            }
+        }
+        (false, fibonacci_index(hash, self.nbuckets))
+    }
+}
+
+impl<K, V, S: Default> Default for IncrementalHashMap<K, V, S> {
+    fn default() -> Self {
+        IncrementalHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> IncrementalHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // splits up to `steps` old buckets into the new table
+    fn migrate(&mut self, steps: usize) {
+        for _ in 0..steps {
+            let Some(old_bucket) = self.old_buckets.pop_front() else {
+                break;
+            };
+            // old bucket i becomes buckets 2i and 2i + 1, which are the next two to exist
+            let first = self.buckets.len();
+            self.buckets.push(Vec::new());
+            self.buckets.push(Vec::new());
+            for (key, value) in old_bucket {
+                let bucket = fibonacci_index(self.hash_builder.hash_one(&key), self.nbuckets);
+                debug_assert!(bucket == first || bucket == first + 1);
+                self.buckets[bucket].push((key, value));
+            }
+            self.migrated += 1;
+        }
+
+        if self.old_buckets.is_empty() {
+            // every old bucket was popped, so this only frees the deque's buffer
+            self.old_buckets = VecDeque::new();
+            self.migrated = 0;
+        }
+    }
+
+    // starts moving to a table twice the size, without moving anything yet. the new table only
+    // reserves its memory here, its buckets are pushed as the old ones are split
+    fn start_resize(&mut self) {
+        // the step size makes this a no-op in practice, it only guards against two tables in flight
+        self.migrate(usize::MAX);
+
+        if self.nbuckets == 0 {
+            self.buckets.extend((0..INITIAL_NBUCKETS).map(|_| Vec::new()));
+            self.nbuckets = INITIAL_NBUCKETS;
+            return;
+        }
+        self.nbuckets *= 2;
+        let new_buckets = Vec::with_capacity(self.nbuckets);
+        // turning a Vec into a VecDeque reuses its buffer without copying
+        self.old_buckets = VecDeque::from(mem::replace(&mut self.buckets, new_buckets));
+    }
+
+    // table, bucket and position of the key
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<(bool, usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.nbuckets == 0 {
+            return None;
+        }
+        let (old, bucket) = self.locate(hash);
+        let chain = if old {
+            &self.old_buckets[bucket]
+        } else {
+            &self.buckets[bucket]
+        };
+        let pos = chain.iter().position(|(ekey, _)| ekey.borrow() == key)?;
+        Some((old, bucket, pos))
+    }
+
+    fn chain(&mut self, old: bool, bucket: usize) -> &mut Vec<(K, V)> {
+        if old {
+            &mut self.old_buckets[bucket]
+        } else {
+            &mut self.buckets[bucket]
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.migrate(MIGRATE_STEP);
+
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some((old, bucket, pos)) = self.find(hash, &key) {
+            return Some(mem::replace(&mut self.chain(old, bucket)[pos].1, value));
+        }
+
+        if (self.items + 1) * 4 > self.nbuckets * 3 {
+            self.start_resize();
+        }
+        // a key whose old bucket hasn't been split yet goes into it and moves with it later
+        let (old, bucket) = self.locate(hash);
+        self.chain(old, bucket).push((key, value));
+        self.items += 1;
+        None
+    }
+
+    // doesn't move any buckets: migrating needs &mut self, and taking that here would rule out
+    // holding several gets at once. a run of lookups alone doesn't grow the map anyway, so the
+    // next insert, get_mut or remove picks the migration up where it was left
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (old, bucket, pos) = self.find(self.hash_builder.hash_one(key), key)?;
+        let chain = if old {
+            &self.old_buckets[bucket]
+        } else {
+            &self.buckets[bucket]
+        };
+        Some(&chain[pos].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.migrate(MIGRATE_STEP);
+
+        let (old, bucket, pos) = self.find(self.hash_builder.hash_one(key), key)?;
+        Some(&mut self.chain(old, bucket)[pos].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hash_builder.hash_one(key), key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.migrate(MIGRATE_STEP);
+
+        let (old, bucket, pos) = self.find(self.hash_builder.hash_one(key), key)?;
+        let (_, value) = self.chain(old, bucket).swap_remove(pos);
+        self.items -= 1;
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_during_migration() {
+        let mut map = IncrementalHashMap::new();
+        let mut seen_migration = false;
+        for i in 0..10_000u32 {
+            map.insert(i, i);
+            if map.is_migrating() {
+                seen_migration = true;
+                // everything inserted so far is reachable whichever table it is in
+                assert_eq!(map.get(&(i / 2)), Some(&(i / 2)));
+                if i % 64 == 0 {
+                    assert_eq!(map.iter().count(), map.len());
+                }
+            }
+        }
+        assert!(seen_migration);
+
+        for i in (0..10_000u32).step_by(3) {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+        for i in 0..10_000u32 {
+            assert_eq!(map.get(&i), (i % 3 != 0).then_some(&i));
+        }
+    }
+
+    #[test]
+    fn each_insert_does_a_bounded_amount_of_work() {
+        let mut map = IncrementalHashMap::new();
+        let (mut starts, mut ends) = (0, 0);
+        for i in 0..100_000u32 {
+            let was_migrating = map.is_migrating();
+            let (old_left, new_made) = (map.old_buckets.len(), map.buckets.len());
+            let (old_cap, new_cap) = (map.old_buckets.capacity(), map.buckets.capacity());
+            map.insert(i, i);
+
+            if !was_migrating && map.is_migrating() {
+                // starting a resize only reserves the new table: it has no buckets yet and
+                // the old one is the same allocation as before
+                starts += 1;
+                assert!(map.buckets.is_empty());
+                assert!(map.buckets.capacity() >= map.nbuckets);
+                assert_eq!(map.old_buckets.capacity(), new_cap);
+                continue;
+            }
+            if was_migrating {
+                // at most one step of old buckets split, two new buckets made for each
+                let moved = old_left - map.old_buckets.len();
+                assert!(moved <= MIGRATE_STEP);
+                assert_eq!(map.buckets.len() - new_made, 2 * moved);
+                if map.is_migrating() {
+                    assert_eq!(map.old_buckets.capacity(), old_cap);
+                } else {
+                    // finishing frees an old table that was already emptied bucket by bucket
+                    ends += 1;
+                    assert!(old_left <= MIGRATE_STEP);
+                    assert_eq!(map.buckets.len(), map.nbuckets);
+                }
+            }
+        }
+        assert!(starts >= 10);
+        assert!(ends >= starts - 1);
+        assert_eq!(map.len(), 100_000);
+        assert_eq!(map.iter().count(), 100_000);
+    }
+
+    #[test]
+    fn split_buckets_stay_findable() {
+        // get_mut and remove also move buckets along, and lookups see every key whichever
+        // table it is in while a resize runs
+        let mut map = IncrementalHashMap::new();
+        for i in 0..13u32 {
+            map.insert(i, i);
+        }
+        map.insert(13, 13);
+        assert!(map.is_migrating());
+        while map.is_migrating() {
+            for i in 0..14u32 {
+                assert_eq!(map.get(&i), Some(&i));
+            }
+            *map.get_mut(&0).unwrap() += 0;
+        }
+        assert_eq!(map.nbuckets, 32);
+        assert_eq!(map.remove(&5), Some(5));
+        assert_eq!(map.iter().count(), 13);
+    }
+}
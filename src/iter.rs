@@ -0,0 +1,428 @@
+use core::iter::FusedIterator;
+use core::slice;
+
+use crate::alloc_vec::AllocVec;
+use crate::{Allocator, Global, HashMap, Slot};
+
+// walks the buckets one after another, empty buckets just hand over to the next one straight away
+pub struct Iter<'a, K, V, A: Allocator = Global> {
+    buckets: slice::Iter<'a, AllocVec<Slot<K, V>, A>>,
+    bucket: slice::Iter<'a, Slot<K, V>>,
+    remaining: usize,
+}
+
+pub struct IterMut<'a, K, V, A: Allocator = Global> {
+    buckets: slice::IterMut<'a, AllocVec<Slot<K, V>, A>>,
+    bucket: slice::IterMut<'a, Slot<K, V>>,
+    remaining: usize,
+}
+
+// owns the bucket table and hands out the pairs by value, emptying it one bucket at a time
+pub struct IntoIter<K, V, A: Allocator = Global> {
+    buckets: AllocVec<AllocVec<Slot<K, V>, A>, A>,
+    bucket: Option<AllocVec<Slot<K, V>, A>>,
+    remaining: usize,
+}
+
+pub struct Keys<'a, K, V, A: Allocator = Global> {
+    inner: Iter<'a, K, V, A>,
+}
+
+pub struct Values<'a, K, V, A: Allocator = Global> {
+    inner: Iter<'a, K, V, A>,
+}
+
+pub struct ValuesMut<'a, K, V, A: Allocator = Global> {
+    inner: IterMut<'a, K, V, A>,
+}
+
+// empties the map as it goes, bucket by bucket, keeping every bucket allocated for reuse.
+// pairs are popped one at a time and the item count follows along, so the map is consistent
+// whenever control returns to the caller. dropping it early drops whatever is left.
+pub struct Drain<'a, K, V, A: Allocator = Global> {
+    buckets: &'a mut [AllocVec<Slot<K, V>, A>],
+    items: &'a mut usize,
+    bucket: usize,
+}
+
+// removes and yields the pairs the predicate picks, keeping the rest. each pair is taken out of
+// its bucket with swap_remove the moment it is picked, so a panicking predicate or an early
+// drop leaves the map consistent, with the pairs that weren't visited still in it
+pub struct ExtractIf<'a, K, V, F, A: Allocator = Global> {
+    buckets: &'a mut [AllocVec<Slot<K, V>, A>],
+    items: &'a mut usize,
+    bucket: usize,
+    index: usize,
+    pred: F,
+}
+
+pub struct IntoKeys<K, V, A: Allocator = Global> {
+    inner: IntoIter<K, V, A>,
+}
+
+pub struct IntoValues<K, V, A: Allocator = Global> {
+    inner: IntoIter<K, V, A>,
+}
+
+impl<K, V, S, A: Allocator> HashMap<K, V, S, A> {
+    // iterates over the pairs in bucket order, which is arbitrary
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        Iter {
+            buckets: self.buckets.iter(),
+            bucket: [].iter(),
+            remaining: self.items,
+        }
+    }
+
+    // same as iter but the values can be changed in place
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, A> {
+        IterMut {
+            buckets: self.buckets.iter_mut(),
+            bucket: [].iter_mut(),
+            remaining: self.items,
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V, A> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V, A> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, A> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    // takes every pair out of the map but keeps the buckets allocated
+    pub fn drain(&mut self) -> Drain<'_, K, V, A> {
+        Drain {
+            buckets: &mut self.buckets,
+            items: &mut self.items,
+            bucket: 0,
+        }
+    }
+
+    // lazily removes the pairs for which `pred` returns true, see ExtractIf
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F, A>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        ExtractIf {
+            buckets: &mut self.buckets,
+            items: &mut self.items,
+            bucket: 0,
+            index: 0,
+            pred,
+        }
+    }
+
+    // consumes the map and yields only the keys
+    pub fn into_keys(self) -> IntoKeys<K, V, A> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    // consumes the map and yields only the values
+    pub fn into_values(self) -> IntoValues<K, V, A> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V, S, A: Allocator> IntoIterator for &'a HashMap<K, V, S, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+
+    fn into_iter(self) -> Iter<'a, K, V, A> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S, A: Allocator> IntoIterator for &'a mut HashMap<K, V, S, A> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, A>;
+
+    fn into_iter(self) -> IterMut<'a, K, V, A> {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, S, A: Allocator> IntoIterator for HashMap<K, V, S, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    fn into_iter(self) -> IntoIter<K, V, A> {
+        IntoIter {
+            buckets: self.buckets,
+            bucket: None,
+            remaining: self.items,
+        }
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for Iter<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the remaining buckets are all empty once the last pair is out, no need to walk them
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(slot) = self.bucket.next() {
+                self.remaining -= 1;
+                return Some((&slot.key, &slot.value));
+            }
+            self.bucket = self.buckets.next()?.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for IterMut<'a, K, V, A> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the remaining buckets are all empty once the last pair is out, no need to walk them
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(slot) = self.bucket.next() {
+                self.remaining -= 1;
+                return Some((&slot.key, &mut slot.value));
+            }
+            self.bucket = self.buckets.next()?.iter_mut();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, A: Allocator> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        // the empty buckets left over are freed with the table when the iterator drops
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(slot) = self.bucket.as_mut().and_then(AllocVec::pop) {
+                self.remaining -= 1;
+                return Some((slot.key, slot.value));
+            }
+            self.bucket = Some(self.buckets.pop()?);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, A: Allocator> Iterator for Drain<'_, K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        if *self.items == 0 {
+            return None;
+        }
+        while self.bucket < self.buckets.len() {
+            if let Some(slot) = self.buckets[self.bucket].pop() {
+                *self.items -= 1;
+                return Some((slot.key, slot.value));
+            }
+            self.bucket += 1;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (*self.items, Some(*self.items))
+    }
+}
+
+impl<K, V, A: Allocator> Drop for Drain<'_, K, V, A> {
+    fn drop(&mut self) {
+        for bucket in &mut self.buckets[self.bucket..] {
+            bucket.clear();
+        }
+        *self.items = 0;
+    }
+}
+
+impl<K, V, F, A: Allocator> Iterator for ExtractIf<'_, K, V, F, A>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.bucket < self.buckets.len() {
+            let bucket = &mut self.buckets[self.bucket];
+            while self.index < bucket.len() {
+                let slot = &mut bucket[self.index];
+                if (self.pred)(&slot.key, &mut slot.value) {
+                    // the last pair moves into this index and gets looked at next
+                    *self.items -= 1;
+                    let slot = bucket.swap_remove(self.index);
+                    return Some((slot.key, slot.value));
+                }
+                self.index += 1;
+            }
+            self.bucket += 1;
+            self.index = 0;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(*self.items))
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for Keys<'a, K, V, A> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for Values<'a, K, V, A> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for ValuesMut<'a, K, V, A> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, A: Allocator> Iterator for IntoKeys<K, V, A> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, A: Allocator> Iterator for IntoValues<K, V, A> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, A: Allocator> ExactSizeIterator for Iter<'_, K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for IterMut<'_, K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for IntoIter<K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for Keys<'_, K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for Values<'_, K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for ValuesMut<'_, K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for IntoKeys<K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for IntoValues<K, V, A> {}
+impl<K, V, A: Allocator> ExactSizeIterator for Drain<'_, K, V, A> {}
+
+// once the bucket iterator runs dry it keeps returning None, so every iterator here is fused
+impl<K, V, A: Allocator> FusedIterator for Iter<'_, K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for IterMut<'_, K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for IntoIter<K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for Keys<'_, K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for Values<'_, K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for ValuesMut<'_, K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for IntoKeys<K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for IntoValues<K, V, A> {}
+impl<K, V, A: Allocator> FusedIterator for Drain<'_, K, V, A> {}
+impl<K, V, F: FnMut(&K, &mut V) -> bool, A: Allocator> FusedIterator for ExtractIf<'_, K, V, F, A> {}
+
+impl<K, V, A: Allocator> Clone for Iter<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        Iter {
+            buckets: self.buckets.clone(),
+            bucket: self.bucket.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<K, V, A: Allocator> Clone for Keys<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        Keys {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K, V, A: Allocator> Clone for Values<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        Values {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FixedState, HashMap};
+
+    #[test]
+    fn iter_stops_after_the_last_pair() {
+        let mut map = HashMap::with_capacity_and_hasher(10_000, FixedState::with_seed(1));
+        map.insert(1, 1);
+
+        // the pair sits somewhere before the end of the table, the empty buckets after it
+        // aren't visited
+        let mut iter = map.iter();
+        assert_eq!(iter.next(), Some((&1, &1)));
+        let unvisited = iter.buckets.len();
+        assert!(unvisited > 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.buckets.len(), unvisited);
+
+        let mut iter = map.iter_mut();
+        assert!(iter.next().is_some());
+        let unvisited = iter.buckets.len();
+        assert!(iter.next().is_none());
+        assert_eq!(iter.buckets.len(), unvisited);
+    }
+}
@@ -1,117 +1,667 @@
-use std::hash::{DefaultHasher, Hash, Hasher};
-use std::{mem};
-const INITIAL_NBUCKETS: usize = 10;
+// without the std feature the crate only needs core and alloc. tests always get std, they lean
+// on it for String, catch_unwind and thread locals
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
-pub struct HashMap<K, V> {
-    buckets: Vec<Vec<(K, V)>>,
+extern crate alloc;
+
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::{mem, ptr};
+
+use alloc_vec::AllocVec;
+
+mod alloc_vec;
+mod allocator;
+mod array;
+#[cfg(test)]
+mod conformance;
+mod hash;
+mod incremental;
+mod iter;
+mod linked;
+mod open;
+mod policy;
+mod robin_hood;
+#[cfg(feature = "serde")]
+mod serde;
+mod set;
+mod small;
+mod swiss;
+mod traits;
+pub use allocator::{AllocError, Allocator, Global};
+pub use array::ArrayHashMap;
+pub use hash::{FixedState, SipHasher13};
+pub use incremental::IncrementalHashMap;
+pub use iter::{
+    Drain, ExtractIf, IntoIter, IntoKeys, IntoValues, Iter, IterMut, Keys, Values, ValuesMut,
+};
+pub use linked::LinkedHashMap;
+pub use open::OpenHashMap;
+pub use policy::GrowthPolicy;
+pub use robin_hood::RobinHoodHashMap;
+#[cfg(feature = "serde")]
+pub use serde::{DuplicateKeys, HashMapSeed, deserialize_unique_keys};
+pub use set::{
+    Difference, HashSet, Intersection, SetIntoIter, SetIter, SymmetricDifference, Union,
+};
+pub use small::{
+    SmallEntry, SmallHashMap, SmallIntoIter, SmallIter, SmallIterMut, SmallOccupiedEntry,
+    SmallVacantEntry,
+};
+pub use swiss::SwissHashMap;
+
+// the hasher builder every map uses unless it's given another one. with std that is RandomState,
+// which seeds each map with its own random keys. core has no source of randomness, so without
+// std it falls back to FixedState, and maps that hold untrusted keys should be built with
+// with_hasher(FixedState::with_seed(..)) from whatever entropy the platform has
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::hash::RandomState;
+#[cfg(not(feature = "std"))]
+pub type DefaultHashBuilder = FixedState;
+
+// S builds the hashers used for every key, by default RandomState, the same one std uses.
+// every RandomState gets its own random keys, so two maps (or two runs) put the same key in
+// different buckets and nobody can precompute keys that all pile into one bucket.
+// FixedState opts out of that when a reproducible layout is needed.
+// A supplies the memory for the bucket table and every bucket in it, see Allocator.
+pub struct HashMap<K, V, S = DefaultHashBuilder, A: Allocator = Global> {
+    buckets: AllocVec<AllocVec<Slot<K, V>, A>, A>,
     items: usize,
+    hash_builder: S,
+    policy: GrowthPolicy,
+}
+
+// a pair as it sits in a bucket, along with the full hash of its key. keeping the hash means a
+// rehash never calls K::hash again, and a bucket scan only calls K::eq on keys whose hash matches
+#[derive(Clone)]
+struct Slot<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+impl<K, V> Slot<K, V> {
+    // the test every chain scan uses. the stored hash is compared first so K::eq only runs on
+    // likely matches
+    fn matches<Q>(&self, hash: u64, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.hash == hash && self.key.borrow() == key
+    }
 }
 
-impl<K, V> HashMap<K, V> {
+impl<K, V> HashMap<K, V, DefaultHashBuilder> {
     pub fn new() -> Self {
+        HashMap::with_hasher(DefaultHashBuilder::default())
+    }
+
+    // creates a map that can take `capacity` items before it has to rehash
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    // creates an empty map that sizes its table according to `policy`
+    pub fn with_policy(policy: GrowthPolicy) -> Self {
+        HashMap::with_policy_and_hasher(policy, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, A: Allocator + Clone> HashMap<K, V, DefaultHashBuilder, A> {
+    // creates an empty map that allocates from `alloc`
+    pub fn new_in(alloc: A) -> Self {
+        HashMap::with_hasher_in(DefaultHashBuilder::default(), alloc)
+    }
+
+    // same as with_capacity, the buckets for `capacity` items come from `alloc` straight away
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        HashMap::with_capacity_and_hasher_in(capacity, DefaultHashBuilder::default(), alloc)
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    // creates an empty map that hashes its keys with the given builder
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashMap::with_hasher_in(hash_builder, Global)
+    }
+
+    // creates a map with enough buckets for `capacity` items before the first resize
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        HashMap::with_capacity_and_hasher_in(capacity, hash_builder, Global)
+    }
+
+    pub fn with_policy_and_hasher(policy: GrowthPolicy, hash_builder: S) -> Self {
+        HashMap::with_policy_and_hasher_in(policy, hash_builder, Global)
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> HashMap<K, V, S, A> {
+    pub fn with_hasher_in(hash_builder: S, alloc: A) -> Self {
+        HashMap::with_policy_and_hasher_in(GrowthPolicy::new(), hash_builder, alloc)
+    }
+
+    pub fn with_capacity_and_hasher_in(capacity: usize, hash_builder: S, alloc: A) -> Self {
+        let mut map = HashMap::with_hasher_in(hash_builder, alloc);
+        let nbuckets = map.policy.buckets_for(capacity);
+        map.buckets = map.table(nbuckets);
+        map
+    }
+
+    // the constructor the others end up in, nothing is allocated until the first insert
+    pub fn with_policy_and_hasher_in(policy: GrowthPolicy, hash_builder: S, alloc: A) -> Self {
+        policy.validate();
         HashMap {
-            buckets: Vec::new(),
+            buckets: AllocVec::new_in(alloc),
             items: 0,
+            hash_builder,
+            policy,
+        }
+    }
+
+    // the allocator the table and buckets come from
+    pub fn allocator(&self) -> &A {
+        self.buckets.allocator()
+    }
+
+    // the builder this map hashes its keys with
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    // the policy deciding when and how far this map grows or shrinks
+    pub fn policy(&self) -> &GrowthPolicy {
+        &self.policy
+    }
+
+    // number of key value pairs in the map
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    // number of items the map can hold before the next rehash
+    pub fn capacity(&self) -> usize {
+        self.policy.capacity(self.buckets.len())
+    }
+
+    // removes every pair but keeps the buckets allocated so the map can be refilled cheaply
+    pub fn clear(&mut self) {
+        for bucket in self.buckets.iter_mut() {
+            bucket.clear();
+        }
+        self.items = 0;
+    }
+
+    // keeps only the pairs for which `f` returns true, visiting each bucket in place.
+    // with a shrink load in the policy, the table then shrinks in a single rehash
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|key, value| !f(key, value)).for_each(drop);
+        self.shrink_if_sparse();
+    }
+
+    // true when one more item would push the load factor past the policy's max, or there are
+    // no buckets yet
+    fn needs_resize(&self) -> bool {
+        self.policy.should_grow(self.items, self.buckets.len())
+    }
+
+    // halves the table after removals if the policy has a shrink load and the map went under it
+    fn shrink_if_sparse(&mut self) {
+        if let Some(target_size) = self.policy.shrink(self.items, self.buckets.len()) {
+            self.rehash(target_size);
+        }
+    }
+
+    // takes a hash and returns the index of the bucket the pair belongs in
+    // the policy turns the hash into an index, by modulo or by Fibonacci hashing for power of two tables
+    fn bucket(&self, hash: u64) -> usize {
+        self.policy.index(hash, self.buckets.len())
+    }
+
+    // a table of `size` empty buckets. empty buckets don't allocate until something goes in them
+    fn table(&self, size: usize) -> AllocVec<AllocVec<Slot<K, V>, A>, A> {
+        empty_table(self.allocator(), size)
+    }
+
+    fn rehash(&mut self, target_size: usize) {
+        rehash(&mut self.buckets, &self.policy, target_size);
+    }
+}
+
+fn empty_table<K, V, A: Allocator + Clone>(
+    alloc: &A,
+    size: usize,
+) -> AllocVec<AllocVec<Slot<K, V>, A>, A> {
+    let mut table = AllocVec::with_capacity_in(size, alloc.clone());
+    for _ in 0..size {
+        table.push(AllocVec::new_in(alloc.clone()));
+    }
+    table
+}
+
+// moves every pair into a freshly allocated vector of `target_size` buckets, placing them by
+// their stored hash so no key is hashed again. it only needs the table and the policy, so an
+// OccupiedEntry can shrink the map after a removal too
+fn rehash<K, V, A: Allocator + Clone>(
+    buckets: &mut AllocVec<AllocVec<Slot<K, V>, A>, A>,
+    policy: &GrowthPolicy,
+    target_size: usize,
+) {
+    let new_buckets = empty_table(buckets.allocator(), target_size);
+
+    let mut old_buckets = mem::replace(buckets, new_buckets);
+    while let Some(mut old_bucket) = old_buckets.pop() {
+        while let Some(slot) = old_bucket.pop() {
+            let bucket = policy.index(slot.hash, target_size);
+            buckets[bucket].push(slot);
         }
     }
 }
 
-impl<K, V> HashMap<K, V>
+impl<K, V, S: Default, A: Allocator + Clone + Default> Default for HashMap<K, V, S, A> {
+    fn default() -> Self {
+        HashMap::with_hasher_in(S::default(), A::default())
+    }
+}
+
+impl<K, V, S, A> HashMap<K, V, S, A>
 where
     K: Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
 {   
 
-    // takes key and returns bucket's index to insert the key value pair in that specific bucket
-    fn bucket(&self , key: &K) -> usize {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        (hasher.finish() % (self.buckets.len() as u64)) as usize        
+    // the key can be any borrowed form of K, Borrow guarantees it hashes the same as the owned key
+    // this is the only place keys get hashed, the result is stored next to the pair so resizes
+    // don't need to come back here
+    fn hash<Q>(&self, key: &Q) -> u64
+    where
+        Q: Hash + ?Sized,
+    {
+        self.hash_builder.hash_one(key)
     }
 
 
     // finds the hash and puts <key , value> pair in the bucket vector
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        if self.buckets.is_empty() || self.items > 3 * self.buckets.len() / 4 {
+        if self.needs_resize() {
             self.resize();
         }
 
-        let bucket: usize = self.bucket(&key);
+        let hash = self.hash(&key);
+        let bucket: usize = self.bucket(hash);
 
-        let bucket = &mut self.buckets[bucket];
-
-        for &mut (ref ekey, ref mut evalue) in bucket.iter_mut() {
-            if ekey == &key {
-                return Some(mem::replace(evalue, value));
+        match self.position(bucket, hash, &key) {
+            Some(index) => Some(mem::replace(&mut self.buckets[bucket][index].value, value)),
+            None => {
+                self.buckets[bucket].push(Slot { hash, key, value });
+                self.items += 1;
+                None
             }
         }
+    }
 
-        bucket.push((key, value));
-        None
+    // scans a single bucket and returns the position of the key inside it, if present
+    fn position<Q>(&self, bucket: usize, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.buckets[bucket]
+            .iter()
+            .position(|slot| slot.matches(hash, key))
     }
 
+    // hashes the key and finds which bucket and position within it holds the key
+    fn find<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.buckets.is_empty() {
+            return None;
+        }
 
-    // function to resize the buckets inside a hashmap if the capacity exceeds 3/4th of the size of a bucket to optimize search time
-    fn resize(&mut self) {
-        let target_size: usize = match self.buckets.len() {
-            0 => INITIAL_NBUCKETS,
-            n => 2 * n,
-        };
+        let hash = self.hash(key);
+        let bucket = self.bucket(hash);
+        self.position(bucket, hash, key).map(|index| (bucket, index))
+    }
 
-        let mut new_buckets = Vec::with_capacity(target_size);
-        new_buckets.extend((0..target_size).map(|_| Vec::new()));
+    // gets the entry for the key so it can be inspected or filled in with a single hash and scan
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, A> {
+        if self.needs_resize() {
+            self.resize();
+        }
+
+        let hash = self.hash(&key);
+        let bucket = self.bucket(hash);
 
-        for (key, value) in self.buckets.iter_mut().flat_map(|bucket| bucket.drain(..)) {
-            let mut hasher = DefaultHasher::new();
-            key.hash(&mut hasher);
-            let bucket = (hasher.finish() % new_buckets.len() as u64) as usize;
-            new_buckets[bucket].push((key, value));
+        match self.position(bucket, hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                table: &mut self.buckets,
+                items: &mut self.items,
+                policy: &self.policy,
+                bucket,
+                index,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                hash,
+                key,
+                table: &mut self.buckets,
+                items: &mut self.items,
+                policy: &self.policy,
+                bucket,
+            }),
         }
-        mem::replace(&mut self.buckets, new_buckets);
+    }
+
+
+    // function to resize the buckets inside a hashmap if the load goes over the policy's max load to optimize search time
+    fn resize(&mut self) {
+        let target_size: usize = self.policy.grow(self.buckets.len());
+        self.rehash(target_size);
     }
 
     
-    // get the value from the key 
-    pub fn get(&self , key: &K) -> Option<&V> {
-        if self.buckets.is_empty() {
-            return None;
+    // get the value from the key, the key may be any borrowed form of K (e.g. &str for String keys)
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (bucket, index) = self.find(key)?;
+        Some(&self.buckets[bucket][index].value)
+    }
+
+    // get a mutable reference to the value so it can be updated in place
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (bucket, index) = self.find(key)?;
+        Some(&mut self.buckets[bucket][index].value)
+    }
+
+    // checks whether the key is in the map
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    // get the stored key along with the value, useful when equal keys can still differ
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (bucket, index) = self.find(key)?;
+        let slot = &self.buckets[bucket][index];
+        Some((&slot.key, &slot.value))
+    }
+
+    // get mutable references to the values of several keys at once, e.g. both sides of a transfer.
+    // panics if two of the keys are equal, since that would hand out two &mut to the same value
+    pub fn get_disjoint_mut<Q, const N: usize>(&mut self, keys: &[&Q; N]) -> [Option<&mut V>; N]
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let found = keys.map(|key| self.find(key));
+        for (i, location) in found.iter().enumerate() {
+            if location.is_some() && found[..i].contains(location) {
+                panic!("duplicate keys passed to get_disjoint_mut");
+            }
         }
 
-        let bucket = self.bucket(key);
-        self.buckets[bucket]
-            .iter()
-            .find(|&(ekey,_)|ekey == key)
-            .map(|&(_,ref v)|v)
+        // indexing through the buckets would create a &mut [Slot] over a whole bucket for every
+        // key, invalidating the references already handed out for other keys in the same bucket.
+        // raw pointers are taken into each bucket's buffer instead
+        let table = self.buckets.as_mut_ptr();
+        found.map(|location| {
+            let (bucket, index) = location?;
+            // SAFETY: find returned in-bounds positions and every location is distinct, checked
+            // above, so no two references alias. the &mut to the bucket itself only covers its
+            // pointer and length, not the slots. the buffers aren't moved or freed while self
+            // stays mutably borrowed for the lifetime of the result
+            unsafe {
+                let slot = (*table.add(bucket)).as_mut_ptr().add(index);
+                Some(&mut *ptr::addr_of_mut!((*slot).value))
+            }
+        })
+    }
 
+
+    // makes room for at least `additional` more items so that inserting them won't rehash
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.items.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            // never grow by less than the usual growth step so repeated small reserves stay cheap
+            let target_size = self.policy.buckets_for(needed);
+            self.rehash(target_size.max(self.policy.grow(self.buckets.len())));
+        }
     }
 
+    // shrinks the table as far as the load factor allows for the items currently stored
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
 
-    // Return the capacity of the Hashmap without reallocating space
-    pub fn capacity(&self)-> usize {
-        self.buckets.capacity()
+    // shrinks the table but keeps room for at least `min_capacity` items
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let target_size = self.policy.buckets_for(self.items.max(min_capacity));
+        if target_size < self.buckets.len() {
+            self.rehash(target_size);
+        }
     }
 
     //Removes a key from the hashmap, returning the value at the key if the key was previously in the Hashmap
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        if self.buckets.is_empty() {
-            return None;
-        }
-        
-        let bucket = self.bucket(key);
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    // same as remove, but also hands back the key that was stored
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // Find the bucket and the position of the key inside it
+        let (bucket, pos) = self.find(key)?;
         
-        // Find the position of the key in the bucket with matching pattern
-        let pos = self.buckets[bucket]
-            .iter()
-            .position(|&(ref k, _)| k == key)?;
+        // Remove the key-value pair and return it
+        let slot = self.buckets[bucket].remove(pos);
+        self.items -= 1;
+
+        self.shrink_if_sparse();
         
-        // Remove the key-value pair and return the value
-        let (_, value) = self.buckets[bucket].remove(pos);
-        if self.items > 0 {
-            self.items -= 1;
+        Some((slot.key, slot.value))
+    }
+
+}
+
+
+// a view into a single slot of the map, either holding a value already or waiting for one
+pub enum Entry<'a, K, V, A: Allocator = Global> {
+    Occupied(OccupiedEntry<'a, K, V, A>),
+    Vacant(VacantEntry<'a, K, V, A>),
+}
+
+// an entry whose key is already in the map, remembered by its bucket and its position in it.
+// it holds the whole table and the policy, so removing through it can shrink the map like remove
+pub struct OccupiedEntry<'a, K, V, A: Allocator = Global> {
+    table: &'a mut AllocVec<AllocVec<Slot<K, V>, A>, A>,
+    items: &'a mut usize,
+    policy: &'a GrowthPolicy,
+    bucket: usize,
+    index: usize,
+}
+
+// an entry whose key is missing, holding on to the bucket the key hashed to
+pub struct VacantEntry<'a, K, V, A: Allocator = Global> {
+    hash: u64,
+    key: K,
+    table: &'a mut AllocVec<AllocVec<Slot<K, V>, A>, A>,
+    items: &'a mut usize,
+    policy: &'a GrowthPolicy,
+    bucket: usize,
+}
+
+impl<'a, K, V, A: Allocator> Entry<'a, K, V, A> {
+    // returns the value for the key, inserting the default if the entry is vacant
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    // same as or_insert but only builds the value when the entry is vacant
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
         }
-        
-        Some(value)
     }
 
+    // same as or_insert_with but the closure gets to look at the key
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    // runs the closure on the value if the entry is occupied, leaving vacant entries alone
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+
+    // sets the value of the entry, whether or not it was occupied, and hands back the occupied entry
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, A> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.insert(value);
+                entry
+            }
+            Entry::Vacant(entry) => entry.insert_entry(value),
+        }
+    }
+
+    // the key this entry was made for
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<'a, K, V: Default, A: Allocator> Entry<'a, K, V, A> {
+    // returns the value for the key, inserting V::default() if the entry is vacant
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K, V, A: Allocator> OccupiedEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        &self.table[self.bucket][self.index].key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.table[self.bucket][self.index].value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.table[self.bucket][self.index].value
+    }
+
+    // turns the entry into a reference to the value that lives as long as the map borrow
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.table[self.bucket][self.index].value
+    }
+
+    // replaces the value and returns the old one
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+}
+
+// removing can rehash the table, which needs to clone the allocator for the new buckets
+impl<K, V, A: Allocator + Clone> OccupiedEntry<'_, K, V, A> {
+    // takes the pair out of the map, shrinking it afterwards if the policy asks for that
+    pub fn remove_entry(self) -> (K, V) {
+        *self.items -= 1;
+        let slot = self.table[self.bucket].swap_remove(self.index);
+        if let Some(target_size) = self.policy.shrink(*self.items, self.table.len()) {
+            rehash(self.table, self.policy, target_size);
+        }
+        (slot.key, slot.value)
+    }
+
+    // takes the value out of the map
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+impl<'a, K, V, A: Allocator> VacantEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    // gives the key back without inserting anything
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    // puts the value in the bucket the key hashed to and returns a reference to it
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+
+    // same as insert but returns the now occupied entry
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, A> {
+        let bucket = &mut self.table[self.bucket];
+        let index = bucket.len();
+        bucket.push(Slot {
+            hash: self.hash,
+            key: self.key,
+            value,
+        });
+        *self.items += 1;
+        OccupiedEntry {
+            table: self.table,
+            items: self.items,
+            policy: self.policy,
+            bucket: self.bucket,
+            index,
+        }
+    }
 }
 
 
@@ -127,6 +677,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::hash::RandomState;
     #[test]
     fn insert() {
         let mut map = HashMap::new();
@@ -141,7 +692,7 @@ mod tests {
     }
     #[test]
     fn get_empty () {
-        let mut map: HashMap<&'static str, u32> = HashMap::new();
+        let map: HashMap<&'static str, u32> = HashMap::new();
 
         assert_eq!(map.get(&"abc") , None);
     }
@@ -151,7 +702,8 @@ mod tests {
         let mut map = HashMap::new();
         map.insert(String::from("a"), 1);
 
-        assert_eq!(map.capacity(), 10);
+        // 10 buckets hold 7 items at a load factor of 3/4
+        assert_eq!(map.capacity(), 7);
     }
 
     #[test]
@@ -162,4 +714,713 @@ mod tests {
 
         assert_eq!(remove_value_key, Some(100));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn entry_or_insert() {
+        let mut map = HashMap::new();
+        for word in ["a", "b", "a", "c", "a"] {
+            *map.entry(word).or_insert(0) += 1;
+        }
+
+        assert_eq!(map.get(&"a"), Some(&3));
+        assert_eq!(map.get(&"b"), Some(&1));
+        assert_eq!(map.get(&"d"), None);
+    }
+
+    #[test]
+    fn entry_and_modify() {
+        let mut map = HashMap::new();
+        map.entry("abc").and_modify(|v| *v += 1).or_insert(10);
+        map.entry("abc").and_modify(|v| *v += 1).or_insert(10);
+
+        assert_eq!(map.get(&"abc"), Some(&11));
+    }
+
+    #[test]
+    fn entry_or_default_and_with_key() {
+        let mut map: HashMap<&str, Vec<u32>> = HashMap::new();
+        map.entry("abc").or_default().push(1);
+        map.entry("abc").or_default().push(2);
+        let len = *HashMap::new().entry("abcd").or_insert_with_key(|k: &&str| k.len());
+
+        assert_eq!(map.get(&"abc"), Some(&vec![1, 2]));
+        assert_eq!(len, 4);
+    }
+
+    #[test]
+    fn entry_remove() {
+        let mut map = HashMap::new();
+        map.insert(1, "one");
+        map.insert(2, "two");
+
+        match map.entry(1) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), "one"),
+            Entry::Vacant(_) => panic!("key 1 should be present"),
+        }
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&2), Some(&"two"));
+    }
+
+    #[test]
+    fn entry_insert_entry() {
+        let mut map = HashMap::new();
+        let entry = map.entry("abc").insert_entry(1);
+        assert_eq!(entry.get(), &1);
+
+        let mut entry = map.entry("abc").insert_entry(2);
+        assert_eq!(entry.insert(3), 2);
+        assert_eq!(map.get(&"abc"), Some(&3));
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut map = HashMap::new();
+        map.insert(String::from("abc"), 1);
+        map.insert(String::from("def"), 2);
+
+        assert_eq!(map.get("abc"), Some(&1));
+        assert!(map.contains_key("def"));
+        assert!(!map.contains_key("ghi"));
+
+        *map.get_mut("def").unwrap() += 10;
+        assert_eq!(map.get("def"), Some(&12));
+
+        assert_eq!(map.remove("abc"), Some(1));
+        assert_eq!(map.get("abc"), None);
+    }
+
+    #[test]
+    fn lookups_on_empty_map() {
+        let mut map: HashMap<String, u32> = HashMap::new();
+
+        assert_eq!(map.get_mut("abc"), None);
+        assert_eq!(map.remove("abc"), None);
+        assert!(!map.contains_key("abc"));
+    }
+
+    #[test]
+    fn iter_visits_every_pair() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i * 2);
+        }
+
+        let mut pairs: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        pairs.sort();
+        assert_eq!(pairs, (0..100).map(|i| (i, i * 2)).collect::<Vec<_>>());
+        assert_eq!(map.iter().len(), 100);
+        assert_eq!(map.keys().sum::<i32>(), (0..100).sum::<i32>());
+        assert_eq!(map.values().sum::<i32>(), (0..100).map(|i| i * 2).sum::<i32>());
+    }
+
+    #[test]
+    fn iter_mut_and_values_mut() {
+        let mut map = HashMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+
+        for (_, v) in map.iter_mut() {
+            *v *= 10;
+        }
+        for v in &mut map {
+            *v.1 += 1;
+        }
+        for v in map.values_mut() {
+            *v += 1;
+        }
+
+        assert_eq!(map.get("a"), Some(&12));
+        assert_eq!(map.get("b"), Some(&22));
+    }
+
+    #[test]
+    fn into_iter_and_friends() {
+        let mut map = HashMap::new();
+        map.insert(1, "one");
+        map.insert(2, "two");
+
+        let mut keys: Vec<_> = map.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, [1, 2]);
+
+        let iter = map.into_iter();
+        assert_eq!(iter.len(), 2);
+        let mut pairs: Vec<_> = iter.collect();
+        pairs.sort();
+        assert_eq!(pairs, [(1, "one"), (2, "two")]);
+
+        let mut map = HashMap::new();
+        map.insert(1, "one");
+        assert_eq!(map.into_values().collect::<Vec<_>>(), ["one"]);
+    }
+
+    #[test]
+    fn iter_is_fused_and_exact() {
+        let empty: HashMap<u32, u32> = HashMap::new();
+        assert_eq!(empty.iter().next(), None);
+
+        let mut map = HashMap::new();
+        map.insert(1, 1);
+        map.remove(&1);
+        map.insert(2, 2);
+
+        let mut iter = map.iter();
+        assert_eq!(iter.size_hint(), (1, Some(1)));
+        assert_eq!(iter.next(), Some((&2, &2)));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn custom_hasher() {
+        use std::hash::{BuildHasherDefault, DefaultHasher};
+
+        let mut map: HashMap<&str, u32, BuildHasherDefault<DefaultHasher>> = HashMap::default();
+        for (i, key) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            map.insert(key, i as u32);
+        }
+
+        assert_eq!(map.get("c"), Some(&2));
+        assert_eq!(map.hasher(), &BuildHasherDefault::default());
+    }
+
+    #[test]
+    fn with_capacity_and_hasher() {
+        let mut map = HashMap::with_capacity_and_hasher(100, RandomState::new());
+        let buckets = map.buckets.len();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(map.buckets.len(), buckets);
+        assert_eq!(map.get(&42), Some(&42));
+    }
+
+    // which bucket each key landed in, used to compare layouts between maps
+    fn layout<S: BuildHasher>(map: &HashMap<u32, (), S>) -> Vec<usize> {
+        (0..1000).map(|key| map.bucket(map.hash(&key))).collect()
+    }
+
+    fn filled<S: BuildHasher>(hash_builder: S) -> HashMap<u32, (), S> {
+        let mut map = HashMap::with_hasher(hash_builder);
+        for key in 0..1000 {
+            map.insert(key, ());
+        }
+        map
+    }
+
+    #[test]
+    fn random_state_seeds_each_map() {
+        let first = filled(RandomState::new());
+        let second = filled(RandomState::new());
+
+        assert_eq!(first.buckets.len(), second.buckets.len());
+        assert_ne!(layout(&first), layout(&second));
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn default_hasher_without_std() {
+        let map: HashMap<u32, u32> = HashMap::new();
+        assert_eq!(map.hasher(), &FixedState::new());
+    }
+
+    #[test]
+    fn fixed_state_is_deterministic() {
+        let first = filled(FixedState::with_seed(7));
+        let second = filled(FixedState::with_seed(7));
+        let other_seed = filled(FixedState::with_seed(8));
+
+        assert_eq!(layout(&first), layout(&second));
+        assert_ne!(layout(&first), layout(&other_seed));
+    }
+
+    #[test]
+    fn sip_hasher_matches_std() {
+        use std::hash::{BuildHasherDefault, DefaultHasher, Hasher};
+
+        let inputs: [&[u8]; 5] = [b"", b"a", b"abcdefg", b"abcdefgh", b"the quick brown fox jumps"];
+        for input in inputs {
+            let mut std_hasher = DefaultHasher::new();
+            std_hasher.write(input);
+            let mut ours = FixedState::with_keys(0, 0).build_hasher();
+            // split the write in two to exercise the partial word buffering
+            let (head, tail) = input.split_at(input.len() / 3);
+            ours.write(head);
+            ours.write(tail);
+
+            assert_eq!(ours.finish(), std_hasher.finish());
+        }
+        let std_state = BuildHasherDefault::<DefaultHasher>::default();
+        assert_eq!(FixedState::with_keys(0, 0).hash_one("abc"), std_state.hash_one("abc"));
+        assert_eq!(FixedState::with_keys(0, 0).hash_one(42u64), std_state.hash_one(42u64));
+    }
+
+    #[test]
+    fn len_tracks_inserts_and_removes() {
+        let mut map = HashMap::new();
+        assert!(map.is_empty());
+
+        map.insert("a", 1);
+        map.insert("b", 2);
+        map.insert("a", 3);
+        assert_eq!(map.len(), 2);
+
+        *map.entry("c").or_insert(0) += 1;
+        *map.entry("c").or_insert(0) += 1;
+        assert_eq!(map.len(), 3);
+
+        map.remove("a");
+        map.remove("a");
+        assert_eq!(map.len(), 2);
+
+        if let Entry::Occupied(entry) = map.entry("b") {
+            entry.remove();
+        }
+        assert_eq!(map.len(), 1);
+        assert!(!map.is_empty());
+    }
+
+    #[test]
+    fn load_factor_stays_bounded() {
+        let mut map = HashMap::new();
+        for i in 0..100_000u32 {
+            map.insert(i, i);
+            assert!(map.len() * 4 <= map.buckets.len() * 3);
+        }
+
+        assert_eq!(map.len(), 100_000);
+        // 10 doubled until 3/4 of it holds 100k items
+        assert_eq!(map.buckets.len(), 10 << 14);
+        let longest_chain = map.buckets.iter().map(|bucket| bucket.len()).max().unwrap();
+        assert!(longest_chain <= 16, "longest chain is {longest_chain}");
+    }
+
+    #[test]
+    fn with_capacity_avoids_rehash() {
+        let mut map = HashMap::with_capacity(1000);
+        assert!(map.capacity() >= 1000);
+        let buckets = map.buckets.len();
+
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.buckets.len(), buckets);
+        assert_eq!(HashMap::<u32, u32>::with_capacity(0).capacity(), 0);
+    }
+
+    #[test]
+    fn capacity_is_items_before_rehash() {
+        let mut map = HashMap::new();
+        map.insert(0, 0);
+        let buckets = map.buckets.len();
+
+        for i in 1..map.capacity() {
+            map.insert(i, i);
+        }
+        assert_eq!(map.buckets.len(), buckets);
+
+        map.insert(map.capacity(), 0);
+        assert!(map.buckets.len() > buckets);
+    }
+
+    #[test]
+    fn reserve_grows_once() {
+        let mut map = HashMap::new();
+        map.insert(0, 0);
+        map.reserve(500);
+        assert!(map.capacity() >= 501);
+        let buckets = map.buckets.len();
+
+        for i in 1..501 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.buckets.len(), buckets);
+
+        map.reserve(0);
+        assert_eq!(map.buckets.len(), buckets);
+    }
+
+    #[test]
+    fn shrink_to_and_shrink_to_fit() {
+        let mut map = HashMap::new();
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        for i in 10..1000 {
+            map.remove(&i);
+        }
+
+        map.shrink_to(100);
+        assert!(map.capacity() >= 100);
+        assert!(map.capacity() < 1000);
+
+        map.shrink_to_fit();
+        assert!(map.capacity() >= 10);
+        assert!(map.capacity() < 100);
+        assert_eq!(map.len(), 10);
+        for i in 0..10 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+
+        for i in 0..10 {
+            map.remove(&i);
+        }
+        map.shrink_to_fit();
+        assert_eq!(map.capacity(), 0);
+        map.insert(1, 1);
+        assert_eq!(map.get(&1), Some(&1));
+    }
+
+    #[test]
+    fn clear_keeps_buckets() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let buckets = map.buckets.len();
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.buckets.len(), buckets);
+
+        map.insert(1, 1);
+        assert_eq!(map.get(&1), Some(&1));
+    }
+
+    #[test]
+    fn retain_even() {
+        let mut map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let buckets = map.buckets.len();
+        map.retain(|&k, v| {
+            *v += 1;
+            k % 2 == 0
+        });
+
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.iter().count(), 500);
+        assert_eq!(map.get(&2), Some(&3));
+        assert_eq!(map.get(&3), None);
+        assert_eq!(map.buckets.len(), buckets);
+    }
+
+    #[test]
+    fn drain_empties_map() {
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let buckets = map.buckets.len();
+
+        let mut drained: Vec<_> = map.drain().collect();
+        drained.sort();
+        assert_eq!(drained, (0..100).map(|i| (i, i)).collect::<Vec<_>>());
+        assert!(map.is_empty());
+        assert_eq!(map.buckets.len(), buckets);
+
+        // dropping the iterator early still empties the map
+        map.extend((0..100).map(|i| (i, i)));
+        let mut drain = map.drain();
+        assert_eq!(drain.len(), 100);
+        drain.next();
+        drop(drain);
+        assert!(map.is_empty());
+        assert_eq!(map.iter().count(), 0);
+    }
+
+    #[test]
+    fn extract_if_dropped_early() {
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+
+        let taken: Vec<_> = map.extract_if(|&k, _| k % 10 == 0).take(3).collect();
+        assert_eq!(taken.len(), 3);
+        assert_eq!(map.len(), 97);
+        assert_eq!(map.iter().count(), 97);
+        for (key, _) in taken {
+            assert_eq!(map.get(&key), None);
+        }
+
+        let mut rest: Vec<_> = map.extract_if(|&k, _| k % 10 == 0).map(|(k, _)| k).collect();
+        rest.sort();
+        assert_eq!(rest.len(), 7);
+        assert_eq!(map.len(), 90);
+    }
+
+    #[test]
+    fn retain_panic_keeps_map_consistent() {
+        use std::panic::{AssertUnwindSafe, catch_unwind};
+
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let mut visited = 0;
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.retain(|&k, _| {
+                visited += 1;
+                if visited == 50 {
+                    panic!("predicate failed");
+                }
+                k % 2 == 0
+            })
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(map.len(), map.iter().count());
+        assert!(map.len() < 100 && map.len() > 50);
+        for (&key, &value) in &map {
+            assert_eq!(key, value);
+        }
+    }
+
+    #[test]
+    fn get_key_value_and_remove_entry() {
+        let mut map = HashMap::new();
+        map.insert(String::from("abc"), 1);
+
+        assert_eq!(map.get_key_value("abc"), Some((&String::from("abc"), &1)));
+        assert_eq!(map.get_key_value("def"), None);
+        assert_eq!(map.remove_entry("abc"), Some((String::from("abc"), 1)));
+        assert_eq!(map.remove_entry("abc"), None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn get_disjoint_mut_transfer() {
+        let mut balances = HashMap::new();
+        balances.insert("alice", 100);
+        balances.insert("bob", 50);
+
+        if let [Some(from), Some(to)] = balances.get_disjoint_mut(&["alice", "bob"]) {
+            *from -= 30;
+            *to += 30;
+        }
+        assert_eq!(balances.get("alice"), Some(&70));
+        assert_eq!(balances.get("bob"), Some(&80));
+
+        let [missing, bob] = balances.get_disjoint_mut(&["carol", "bob"]);
+        assert_eq!(missing, None);
+        assert_eq!(bob, Some(&mut 80));
+        // missing keys don't count as duplicates of each other
+        assert_eq!(balances.get_disjoint_mut(&["x", "x"]), [None, None]);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate keys")]
+    fn get_disjoint_mut_aliased_keys() {
+        let mut map = HashMap::new();
+        map.insert(1, 1);
+        let _ = map.get_disjoint_mut(&[&1, &1]);
+    }
+
+    #[test]
+    fn get_disjoint_mut_same_bucket() {
+        // a single bucket puts every key in the same chain
+        let policy = GrowthPolicy::new().initial_buckets(1).max_load(100.0);
+        let mut map = HashMap::with_policy(policy);
+        for i in 0..4 {
+            map.insert(i, i * 10);
+        }
+        assert_eq!(map.buckets.len(), 1);
+
+        let [a, b, c] = map.get_disjoint_mut(&[&1, &2, &3]);
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+        *a += 1;
+        *b += 2;
+        *c += 3;
+        *a += *b + *c;
+        assert_eq!(map.get(&1), Some(&(11 + 22 + 33)));
+        assert_eq!(map.get(&2), Some(&22));
+        assert_eq!(map.get(&3), Some(&33));
+    }
+
+    #[test]
+    fn default_policy_matches_fixed_sizing() {
+        let mut map = HashMap::with_policy(GrowthPolicy::default());
+        map.insert(0, 0);
+        assert_eq!(map.buckets.len(), 10);
+        for i in 1..8 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.buckets.len(), 20);
+    }
+
+    #[test]
+    fn dense_policy() {
+        let policy = GrowthPolicy::new().max_load(0.9).growth_factor(1.5).initial_buckets(4);
+        let mut map = HashMap::with_policy(policy);
+        map.insert(0, 0);
+        assert_eq!(map.buckets.len(), 4);
+
+        let mut sizes = vec![4];
+        for i in 1..1000 {
+            map.insert(i, i);
+            assert!(map.len() as f64 <= map.buckets.len() as f64 * 0.9);
+            if sizes.last() != Some(&map.buckets.len()) {
+                sizes.push(map.buckets.len());
+            }
+        }
+        // every step grows by 1.5x, rounded up
+        for pair in sizes.windows(2) {
+            assert_eq!(pair[1], (pair[0] as f64 * 1.5).ceil() as usize);
+        }
+        assert_eq!(map.capacity(), (map.buckets.len() as f64 * 0.9) as usize);
+    }
+
+    #[test]
+    fn sparse_policy() {
+        let mut map = HashMap::with_policy(GrowthPolicy::new().max_load(0.5));
+        for i in 0..1000 {
+            map.insert(i, i);
+            assert!(map.len() * 2 <= map.buckets.len());
+        }
+        assert!(HashMap::<u32, u32>::with_capacity(1000).buckets.len() < map.buckets.len());
+    }
+
+    #[test]
+    fn shrinking_policy() {
+        let mut map = HashMap::with_policy(GrowthPolicy::new().shrink_below(0.25));
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        let grown = map.buckets.len();
+
+        for i in 0..990 {
+            map.remove(&i);
+        }
+        assert!(map.buckets.len() < grown / 10);
+        for i in 990..1000 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+
+        // without a shrink load the table stays where it is
+        let mut map = HashMap::new();
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        for i in 0..990 {
+            map.remove(&i);
+        }
+        assert_eq!(map.buckets.len(), grown);
+    }
+
+    #[test]
+    fn entry_removal_shrinks() {
+        let mut map = HashMap::with_policy(GrowthPolicy::new().shrink_below(0.25));
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        let grown = map.buckets.len();
+
+        for i in 0..990 {
+            match map.entry(i) {
+                Entry::Occupied(entry) => assert_eq!(entry.remove(), i),
+                Entry::Vacant(_) => panic!("{i} went missing"),
+            }
+        }
+        assert_eq!(map.len(), 10);
+        assert!(map.buckets.len() < grown / 10);
+        for i in 990..1000 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn shrinking_has_hysteresis() {
+        let mut map = HashMap::with_policy(GrowthPolicy::new().shrink_below(0.25));
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.buckets.len(), 2560);
+
+        // the first removal under a quarter full halves the table
+        for i in 0..360 {
+            map.remove(&i);
+        }
+        assert_eq!(map.buckets.len(), 2560);
+        map.remove(&360);
+        assert_eq!(map.len(), 639);
+        assert_eq!(map.buckets.len(), 1280);
+
+        // hovering around the threshold doesn't rehash back and forth
+        for _ in 0..1000 {
+            map.insert(0, 0);
+            map.remove(&0);
+            assert_eq!(map.buckets.len(), 1280);
+        }
+    }
+
+    #[test]
+    fn retain_shrinks_in_one_go() {
+        let mut map = HashMap::with_policy(GrowthPolicy::new().shrink_below(0.25));
+        for i in 0..10_000 {
+            map.insert(i, i);
+        }
+        map.retain(|&key, _| key < 10);
+        assert_eq!(map.buckets.len(), 40);
+        for i in 0..10 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+
+        // without a shrink load retain leaves the table alone
+        let mut map: HashMap<u32, u32> = (0..10_000).map(|i| (i, i)).collect();
+        let buckets = map.buckets.len();
+        map.retain(|&key, _| key < 10);
+        assert_eq!(map.buckets.len(), buckets);
+    }
+
+    thread_local! {
+        static HASHES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        static EQS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    // a key that counts how often it gets hashed and compared, on this test's thread only
+    struct Counted(u32);
+
+    impl Hash for Counted {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            HASHES.set(HASHES.get() + 1);
+            self.0.hash(state);
+        }
+    }
+
+    impl PartialEq for Counted {
+        fn eq(&self, other: &Counted) -> bool {
+            EQS.set(EQS.get() + 1);
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Counted {}
+
+    #[test]
+    fn resizing_never_rehashes_keys() {
+        let mut map = HashMap::new();
+        for i in 0..1000 {
+            map.insert(Counted(i), i);
+        }
+        // one hash per insert, none for the resizes on the way up
+        assert_eq!(HASHES.get(), 1000);
+
+        map.reserve(10_000);
+        map.shrink_to_fit();
+        assert_eq!(HASHES.get(), 1000);
+        assert_eq!(map.get(&Counted(500)), Some(&500));
+    }
+
+    #[test]
+    fn scans_compare_hashes_before_keys() {
+        // a single huge bucket, so every lookup scans past all the other keys
+        let policy = GrowthPolicy::new().initial_buckets(1).max_load(1000.0);
+        let mut map = HashMap::with_policy(policy);
+        for i in 0..100 {
+            map.insert(Counted(i), i);
+        }
+        assert_eq!(map.buckets.len(), 1);
+        // only a key with the same hash is ever compared, and no two keys share one here
+        assert_eq!(EQS.get(), 0);
+
+        for i in 0..100 {
+            assert_eq!(map.get(&Counted(i)), Some(&i));
+        }
+        assert_eq!(EQS.get(), 100);
+        assert_eq!(map.get(&Counted(100)), None);
+        assert_eq!(EQS.get(), 100);
+    }
+}
@@ -0,0 +1,531 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::mem;
+
+use crate::{DefaultHashBuilder, GrowthPolicy, Slot};
+
+// marks the end of the order in both directions
+const NONE: usize = usize::MAX;
+
+// a pair with its hash, plus its neighbours in insertion order
+struct Node<K, V> {
+    slot: Slot<K, V>,
+    prev: usize,
+    next: usize,
+}
+
+// same API as HashMap, but iteration follows insertion order instead of the bucket layout, so
+// output is deterministic. the pairs live in a slab of nodes linked into a doubly linked list,
+// and the bucket table holds node indices chained by hash the way HashMap's buckets hold pairs.
+// removing from the middle unlinks the node in O(1) and leaves its slot for the next insert.
+//
+// inserting a key that is already there updates its value and keeps its place in the order,
+// move_to_back and move_to_front reorder explicitly
+pub struct LinkedHashMap<K, V, S = DefaultHashBuilder> {
+    // indexed by the numbers in buckets and in the links, removed nodes leave a None behind
+    nodes: Vec<Option<Node<K, V>>>,
+    // the None slots in nodes, reused before nodes grows
+    free: Vec<usize>,
+    buckets: Vec<Vec<usize>>,
+    head: usize,
+    tail: usize,
+    items: usize,
+    hash_builder: S,
+    policy: GrowthPolicy,
+}
+
+impl<K, V> LinkedHashMap<K, V, DefaultHashBuilder> {
+    pub fn new() -> Self {
+        LinkedHashMap::with_hasher(DefaultHashBuilder::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        LinkedHashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> LinkedHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        LinkedHashMap {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            buckets: Vec::new(),
+            head: NONE,
+            tail: NONE,
+            items: 0,
+            hash_builder,
+            policy: GrowthPolicy::new(),
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = LinkedHashMap::with_hasher(hash_builder);
+        map.nodes.reserve(capacity);
+        map.buckets = empty_buckets(map.policy.buckets_for(capacity));
+        map
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    // number of items the map can hold before the next rehash
+    pub fn capacity(&self) -> usize {
+        self.policy.capacity(self.buckets.len())
+    }
+
+    // iterates over the pairs from the oldest to the newest, or the other way round with rev()
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + ExactSizeIterator {
+        OrderedIter {
+            nodes: &self.nodes,
+            front: self.head,
+            back: self.tail,
+            remaining: self.items,
+        }
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.iter().map(|(_, value)| value)
+    }
+
+    // the oldest pair
+    pub fn front(&self) -> Option<(&K, &V)> {
+        self.iter().next()
+    }
+
+    // the newest pair
+    pub fn back(&self) -> Option<(&K, &V)> {
+        self.iter().next_back()
+    }
+
+    // removes every pair but keeps the bucket table and the node slab allocated
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        self.nodes.clear();
+        self.free.clear();
+        self.head = NONE;
+        self.tail = NONE;
+        self.items = 0;
+    }
+
+    fn node(&self, index: usize) -> &Node<K, V> {
+        self.nodes[index]
+            .as_ref()
+            .expect("links point at live nodes")
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut Node<K, V> {
+        self.nodes[index]
+            .as_mut()
+            .expect("links point at live nodes")
+    }
+
+    fn bucket(&self, hash: u64) -> usize {
+        self.policy.index(hash, self.buckets.len())
+    }
+
+    // takes a node out of the order, leaving it in the slab with dangling links
+    fn unlink(&mut self, index: usize) {
+        let Node { prev, next, .. } = *self.node(index);
+        match prev {
+            NONE => self.head = next,
+            prev => self.node_mut(prev).next = next,
+        }
+        match next {
+            NONE => self.tail = prev,
+            next => self.node_mut(next).prev = prev,
+        }
+    }
+
+    fn link_back(&mut self, index: usize) {
+        let tail = self.tail;
+        let node = self.node_mut(index);
+        node.prev = tail;
+        node.next = NONE;
+        match tail {
+            NONE => self.head = index,
+            tail => self.node_mut(tail).next = index,
+        }
+        self.tail = index;
+    }
+
+    fn link_front(&mut self, index: usize) {
+        let head = self.head;
+        let node = self.node_mut(index);
+        node.prev = NONE;
+        node.next = head;
+        match head {
+            NONE => self.tail = index,
+            head => self.node_mut(head).prev = index,
+        }
+        self.head = index;
+    }
+
+    // unlinks a node, drops it from its bucket and frees its slot
+    fn remove_node(&mut self, index: usize) -> (K, V) {
+        self.unlink(index);
+        let bucket = self.bucket(self.node(index).slot.hash);
+        let chain = &mut self.buckets[bucket];
+        let pos = chain.iter().position(|&i| i == index).unwrap();
+        chain.swap_remove(pos);
+
+        let node = self.nodes[index].take().unwrap();
+        self.free.push(index);
+        self.items -= 1;
+        (node.slot.key, node.slot.value)
+    }
+
+    pub fn pop_front(&mut self) -> Option<(K, V)> {
+        match self.head {
+            NONE => None,
+            head => Some(self.remove_node(head)),
+        }
+    }
+
+    pub fn pop_back(&mut self) -> Option<(K, V)> {
+        match self.tail {
+            NONE => None,
+            tail => Some(self.remove_node(tail)),
+        }
+    }
+
+    // places every node index in a fresh table of `target_size` buckets by its stored hash
+    fn rehash(&mut self, target_size: usize) {
+        self.buckets = empty_buckets(target_size);
+        let mut index = self.head;
+        while index != NONE {
+            let node = self.node(index);
+            let next = node.next;
+            let bucket = self.bucket(node.slot.hash);
+            self.buckets[bucket].push(index);
+            index = next;
+        }
+    }
+}
+
+fn empty_buckets(size: usize) -> Vec<Vec<usize>> {
+    let mut buckets = Vec::with_capacity(size);
+    buckets.extend((0..size).map(|_| Vec::new()));
+    buckets
+}
+
+impl<K, V, S: Default> Default for LinkedHashMap<K, V, S> {
+    fn default() -> Self {
+        LinkedHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> LinkedHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // the slab index of the key's node
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.items == 0 {
+            return None;
+        }
+        self.find_hashed(self.hash_builder.hash_one(key), key)
+    }
+
+    // same as find for a key that is already hashed. the chain is scanned with the same slot
+    // test HashMap's buckets use
+    fn find_hashed<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.items == 0 {
+            return None;
+        }
+        self.buckets[self.bucket(hash)]
+            .iter()
+            .copied()
+            .find(|&index| self.node(index).slot.matches(hash, key))
+    }
+
+    // a new key goes to the back of the order, an existing one keeps its place and gets the
+    // new value. the key is hashed once, for the lookup and for the bucket it goes in
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.find_hashed(hash, &key) {
+            return Some(mem::replace(&mut self.node_mut(index).slot.value, value));
+        }
+        if self.policy.should_grow(self.items, self.buckets.len()) {
+            self.rehash(self.policy.grow(self.buckets.len()));
+        }
+
+        let node = Node {
+            slot: Slot { hash, key, value },
+            prev: NONE,
+            next: NONE,
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(node);
+                index
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        let bucket = self.bucket(hash);
+        self.buckets[bucket].push(index);
+        self.link_back(index);
+        self.items += 1;
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        Some(&self.node(index).slot.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        Some(&mut self.node_mut(index).slot.value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        Some(self.remove_node(index))
+    }
+
+    // makes the key the newest pair, returning false if it isn't in the map
+    pub fn move_to_back<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(index) = self.find(key) else {
+            return false;
+        };
+        self.unlink(index);
+        self.link_back(index);
+        true
+    }
+
+    // makes the key the oldest pair, returning false if it isn't in the map
+    pub fn move_to_front<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(index) = self.find(key) else {
+            return false;
+        };
+        self.unlink(index);
+        self.link_front(index);
+        true
+    }
+}
+
+// walks the links from both ends, counting down so the two ends stop when they meet
+struct OrderedIter<'a, K, V> {
+    nodes: &'a [Option<Node<K, V>>],
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+impl<'a, K, V> OrderedIter<'a, K, V> {
+    fn node(&self, index: usize) -> &'a Node<K, V> {
+        self.nodes[index]
+            .as_ref()
+            .expect("links point at live nodes")
+    }
+}
+
+impl<'a, K, V> Iterator for OrderedIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.node(self.front);
+        self.front = node.next;
+        self.remaining -= 1;
+        Some((&node.slot.key, &node.slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for OrderedIter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.node(self.back);
+        self.back = node.prev;
+        self.remaining -= 1;
+        Some((&node.slot.key, &node.slot.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for OrderedIter<'_, K, V> {}
+
+impl<K, V> FusedIterator for OrderedIter<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedState;
+
+    fn keys<S>(map: &LinkedHashMap<u32, u32, S>) -> Vec<u32> {
+        map.keys().copied().collect()
+    }
+
+    #[test]
+    fn iterates_in_insertion_order() {
+        let mut map = LinkedHashMap::with_hasher(FixedState::with_seed(1));
+        for i in (0..1000).rev() {
+            map.insert(i, i * 2);
+        }
+        // growing the table doesn't touch the order, and updating a key keeps its place
+        map.insert(501, 0);
+        let expected: Vec<u32> = (0..1000).rev().collect();
+        assert_eq!(keys(&map), expected);
+        assert_eq!(map.values().nth(498), Some(&0));
+        assert_eq!(map.keys().rev().nth(501), Some(&501));
+        assert_eq!(map.iter().len(), 1000);
+
+        for i in (0..1000).step_by(2) {
+            assert_eq!(map.remove(&i), Some(i * 2));
+        }
+        let expected: Vec<u32> = (0..1000).rev().filter(|i| i % 2 == 1).collect();
+        assert_eq!(keys(&map), expected);
+
+        // freed slots are reused, new keys still go to the back
+        map.insert(2000, 0);
+        assert_eq!(map.nodes.len(), 1000);
+        assert_eq!(map.back(), Some((&2000, &0)));
+    }
+
+    #[test]
+    fn pops_and_moves() {
+        let mut map = LinkedHashMap::new();
+        for i in 0..5 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.pop_front(), Some((0, 0)));
+        assert_eq!(map.pop_back(), Some((4, 4)));
+        assert_eq!(keys(&map), [1, 2, 3]);
+
+        assert!(map.move_to_back(&1));
+        assert_eq!(keys(&map), [2, 3, 1]);
+        assert!(map.move_to_front(&3));
+        assert_eq!(keys(&map), [3, 2, 1]);
+        assert!(map.move_to_back(&1));
+        assert!(!map.move_to_front(&7));
+        assert_eq!(keys(&map), [3, 2, 1]);
+        assert_eq!(map.front(), Some((&3, &3)));
+
+        // pops keep the lookup table in step with the order
+        assert_eq!(map.pop_front(), Some((3, 3)));
+        assert_eq!(map.get(&3), None);
+        assert_eq!(map.get(&2), Some(&2));
+        assert_eq!(map.pop_back(), Some((1, 1)));
+        assert_eq!(map.pop_back(), Some((2, 2)));
+        assert_eq!(map.pop_back(), None);
+        assert_eq!(map.iter().next(), None);
+
+        map.insert(9, 9);
+        assert_eq!(keys(&map), [9]);
+    }
+
+    thread_local! {
+        static HASHES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    // a key that counts how often it gets hashed, on this test's thread only
+    #[derive(PartialEq, Eq)]
+    struct Counted(u32);
+
+    impl Hash for Counted {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            HASHES.set(HASHES.get() + 1);
+            self.0.hash(state);
+        }
+    }
+
+    #[test]
+    fn insert_hashes_once() {
+        let mut map = LinkedHashMap::new();
+        for i in 0..1000 {
+            map.insert(Counted(i), i);
+        }
+        // one hash per insert, none for the lookup before it or for the resizes
+        assert_eq!(HASHES.get(), 1000);
+        assert_eq!(map.insert(Counted(7), 0), Some(7));
+        assert_eq!(HASHES.get(), 1001);
+    }
+
+    #[test]
+    fn lru_cache() {
+        // the usual use: touch on every hit, evict from the front when full
+        let mut cache = LinkedHashMap::new();
+        for key in [1, 2, 3, 1, 4, 2, 5, 1] {
+            if cache.move_to_back(&key) {
+                continue;
+            }
+            if cache.len() == 3 {
+                cache.pop_front();
+            }
+            cache.insert(key, key);
+        }
+        assert_eq!(keys(&cache), [2, 5, 1]);
+    }
+}
@@ -0,0 +1,294 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::DefaultHashBuilder;
+
+// open addressing keeps the table a power of two so probing can wrap with a mask
+const INITIAL_NSLOTS: usize = 16;
+
+enum Slot<K, V> {
+    Empty,
+    // left behind by remove so probe sequences running through this slot keep going
+    Deleted,
+    Full(K, V),
+}
+
+// same API as HashMap, but every pair lives directly in one slot vector instead of a vector per
+// bucket. collisions probe quadratically (1, 3, 6, 10, ... slots away), which visits every slot
+// of a power of two table exactly once
+pub struct OpenHashMap<K, V, S = DefaultHashBuilder> {
+    slots: Vec<Slot<K, V>>,
+    items: usize,
+    // deleted slots still count towards the load since lookups have to step over them
+    tombstones: usize,
+    hash_builder: S,
+}
+
+impl<K, V> OpenHashMap<K, V, DefaultHashBuilder> {
+    pub fn new() -> Self {
+        OpenHashMap::with_hasher(DefaultHashBuilder::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        OpenHashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+// number of slots needed to hold `capacity` items under the 3/4 load factor
+fn slots_for(capacity: usize) -> usize {
+    if capacity == 0 {
+        return 0;
+    }
+    let nslots = capacity.checked_mul(4).expect("capacity overflow").div_ceil(3);
+    nslots.next_power_of_two().max(INITIAL_NSLOTS)
+}
+
+fn empty_slots<K, V>(nslots: usize) -> Vec<Slot<K, V>> {
+    let mut slots = Vec::with_capacity(nslots);
+    slots.extend((0..nslots).map(|_| Slot::Empty));
+    slots
+}
+
+impl<K, V, S> OpenHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        OpenHashMap {
+            slots: Vec::new(),
+            items: 0,
+            tombstones: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = OpenHashMap::with_hasher(hash_builder);
+        map.slots = empty_slots(slots_for(capacity));
+        map
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    // number of items the map can hold before the next rehash
+    pub fn capacity(&self) -> usize {
+        self.slots.len() * 3 / 4
+    }
+
+    // iterates over the pairs in slot order, which is arbitrary
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots.iter().filter_map(|slot| match slot {
+            Slot::Full(key, value) => Some((key, value)),
+            _ => None,
+        })
+    }
+}
+
+impl<K, V, S: Default> Default for OpenHashMap<K, V, S> {
+    fn default() -> Self {
+        OpenHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> OpenHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // yields the slot indices to try for this key, starting at its home slot
+    fn probe<Q>(&self, key: &Q) -> impl Iterator<Item = usize> + use<K, V, S, Q>
+    where
+        Q: Hash + ?Sized,
+    {
+        let mask = self.slots.len() - 1;
+        let home = self.hash_builder.hash_one(key) as usize;
+        (0..self.slots.len()).map(move |i| home.wrapping_add(i * (i + 1) / 2) & mask)
+    }
+
+    // index of the slot holding the key, if present
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        for index in self.probe(key) {
+            match &self.slots[index] {
+                Slot::Empty => return None,
+                Slot::Full(ekey, _) if ekey.borrow() == key => return Some(index),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if (self.items + self.tombstones + 1) * 4 > self.slots.len() * 3 {
+            self.resize();
+        }
+
+        // reuse the first tombstone on the way, but only once we know the key isn't further on
+        let mut target = None;
+        for index in self.probe(&key) {
+            match &mut self.slots[index] {
+                Slot::Empty => {
+                    target.get_or_insert(index);
+                    break;
+                }
+                Slot::Deleted => {
+                    target.get_or_insert(index);
+                }
+                Slot::Full(ekey, evalue) => {
+                    if *ekey == key {
+                        return Some(mem::replace(evalue, value));
+                    }
+                }
+            }
+        }
+
+        // resize keeps at least one slot empty, so the probe always finds somewhere to go
+        let index = target.expect("open addressing table is full");
+        if let Slot::Deleted = self.slots[index] {
+            self.tombstones -= 1;
+        }
+        self.slots[index] = Slot::Full(key, value);
+        self.items += 1;
+        None
+    }
+
+    // grows the table, or just clears out tombstones when they are what fills it up
+    fn resize(&mut self) {
+        let target_size = match self.slots.len() {
+            0 => INITIAL_NSLOTS,
+            n if self.items * 2 < n => n,
+            n => 2 * n,
+        };
+
+        let old_slots = mem::replace(&mut self.slots, empty_slots(target_size));
+        self.tombstones = 0;
+        for slot in old_slots {
+            if let Slot::Full(key, value) = slot {
+                let index = self
+                    .probe(&key)
+                    .find(|&index| matches!(self.slots[index], Slot::Empty))
+                    .expect("open addressing table is full");
+                self.slots[index] = Slot::Full(key, value);
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &self.slots[self.find(key)?] {
+            Slot::Full(_, value) => Some(value),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        match &mut self.slots[index] {
+            Slot::Full(_, value) => Some(value),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    // takes the pair out and leaves a tombstone so later keys on the same probe path stay reachable
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        match mem::replace(&mut self.slots[index], Slot::Deleted) {
+            Slot::Full(_, value) => {
+                self.items -= 1;
+                self.tombstones += 1;
+                Some(value)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedState;
+
+    #[test]
+    fn many_keys() {
+        let mut map = OpenHashMap::with_hasher(FixedState::with_seed(1));
+        for i in 0..10_000u32 {
+            map.insert(i, i * 2);
+        }
+        assert_eq!(map.len(), 10_000);
+        assert!(map.len() <= map.capacity());
+        assert!(map.slots.len().is_power_of_two());
+
+        for i in (0..10_000u32).step_by(2) {
+            assert_eq!(map.remove(&i), Some(i * 2));
+        }
+        for i in 0..10_000u32 {
+            assert_eq!(map.get(&i), (i % 2 == 1).then_some(&(i * 2)));
+        }
+        assert_eq!(map.iter().count(), 5_000);
+    }
+
+    #[test]
+    fn tombstones_are_reused_and_cleared() {
+        let mut map = OpenHashMap::new();
+        map.insert(0u32, 0);
+        let nslots = map.slots.len();
+
+        // churn through far more keys than the table holds without ever having many live ones
+        for i in 1..10_000u32 {
+            map.insert(i, i);
+            map.remove(&(i - 1));
+        }
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.slots.len(), nslots);
+        assert!(map.items + map.tombstones <= map.capacity());
+        assert_eq!(map.get(&9_999), Some(&9_999));
+    }
+
+    #[test]
+    fn with_capacity_avoids_rehash() {
+        let mut map = OpenHashMap::with_capacity(1000);
+        let nslots = map.slots.len();
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(map.slots.len(), nslots);
+        assert_eq!(map.get(&999), Some(&999));
+    }
+}
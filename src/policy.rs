@@ -0,0 +1,309 @@
+// default sizing, the same numbers the map has always used
+const DEFAULT_MAX_LOAD: f64 = 0.75;
+const DEFAULT_GROWTH_FACTOR: f64 = 2.0;
+const INITIAL_NBUCKETS: usize = 10;
+
+// 2^64 divided by the golden ratio. multiplying by it spreads every input bit over the high bits
+// of the product, and the top log2(buckets) of them pick the bucket (Fibonacci hashing)
+const FIBONACCI: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// decides how a HashMap sizes its bucket table. it is fixed when the map is built, e.g.
+//
+//     let policy = GrowthPolicy::new().max_load(0.9).growth_factor(1.5);
+//     let map: HashMap<u32, u32> = HashMap::with_policy(policy);
+//
+// loads are items per bucket. chaining copes with loads above 1, but every extra item per bucket
+// is another key comparison on lookups
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrowthPolicy {
+    max_load: f64,
+    growth_factor: f64,
+    initial_buckets: usize,
+    shrink_load: Option<f64>,
+    power_of_two: bool,
+}
+
+impl GrowthPolicy {
+    // grows at a 3/4 load by doubling, starts at 10 buckets and never shrinks on its own
+    pub const fn new() -> Self {
+        GrowthPolicy {
+            max_load: DEFAULT_MAX_LOAD,
+            growth_factor: DEFAULT_GROWTH_FACTOR,
+            initial_buckets: INITIAL_NBUCKETS,
+            shrink_load: None,
+            power_of_two: false,
+        }
+    }
+
+    // the table grows before an insert would push the load past this
+    pub fn max_load(mut self, max_load: f64) -> Self {
+        assert!(max_load > 0.0, "max load must be positive");
+        self.max_load = max_load;
+        self
+    }
+
+    // how many times bigger the table gets each time it grows
+    pub fn growth_factor(mut self, growth_factor: f64) -> Self {
+        assert!(growth_factor > 1.0, "growth factor must be greater than 1");
+        self.growth_factor = growth_factor;
+        self
+    }
+
+    // bucket count of the first table, and the smallest one the map will size itself to
+    pub fn initial_buckets(mut self, initial_buckets: usize) -> Self {
+        assert!(initial_buckets > 0, "initial bucket count must be positive");
+        self.initial_buckets = initial_buckets;
+        self
+    }
+
+    // halves the table whenever a removal takes the load below this, so a map that held millions
+    // of items and drained back down to a few gives the memory back. it has to stay under half the
+    // max load: a halved table then still has room before it grows again, and inserts and removes
+    // alternating around the threshold don't rehash back and forth. that is checked when the
+    // policy is handed to a map, so the builder calls can come in any order
+    pub fn shrink_below(mut self, shrink_load: f64) -> Self {
+        assert!(shrink_load > 0.0, "shrink load must be positive");
+        self.shrink_load = Some(shrink_load);
+        self
+    }
+
+    // checks the settings that depend on each other, once they are all in
+    pub(crate) fn validate(&self) {
+        if let Some(shrink_load) = self.shrink_load {
+            assert!(
+                shrink_load * 2.0 < self.max_load,
+                "shrink load must be below half the max load"
+            );
+        }
+    }
+
+    // keeps the bucket count a power of two so a key's bucket is picked with a multiply and a shift
+    // instead of a 64 bit modulo. masking off the low bits of the hash would be cheaper still, but
+    // weak hashers (identity hashes of integers, say) leave those clustered, so the hash goes
+    // through Fibonacci hashing and the top bits of the product pick the bucket.
+    // sizes round up to the next power of two, so the default 10, 20, 40... becomes 16, 32, 64...
+    pub fn power_of_two(mut self) -> Self {
+        self.power_of_two = true;
+        self
+    }
+
+    // picks the bucket for a hash in a table of `buckets`
+    pub(crate) fn index(&self, hash: u64, buckets: usize) -> usize {
+        if self.power_of_two {
+            fibonacci_index(hash, buckets)
+        } else {
+            (hash % buckets as u64) as usize
+        }
+    }
+
+    // rounds a bucket count up to what this policy allows
+    fn round(&self, buckets: usize) -> usize {
+        if self.power_of_two {
+            buckets
+                .checked_next_power_of_two()
+                .expect("capacity overflow")
+        } else {
+            buckets
+        }
+    }
+
+    // number of items a table of `buckets` holds at the max load
+    pub(crate) fn capacity(&self, buckets: usize) -> usize {
+        (buckets as f64 * self.max_load) as usize
+    }
+
+    // smallest table that holds `capacity` items at the max load
+    pub(crate) fn buckets_for(&self, capacity: usize) -> usize {
+        if capacity == 0 {
+            return 0;
+        }
+        let mut buckets = ceil(capacity as f64 / self.max_load);
+        // float rounding can leave the division one bucket short
+        while self.capacity(buckets) < capacity {
+            buckets = buckets.checked_add(1).expect("capacity overflow");
+        }
+        self.round(buckets.max(self.initial_buckets))
+    }
+
+    // true when one more item would go over the max load
+    pub(crate) fn should_grow(&self, items: usize, buckets: usize) -> bool {
+        items + 1 > self.capacity(buckets)
+    }
+
+    // bucket count to grow to from `buckets`
+    pub(crate) fn grow(&self, buckets: usize) -> usize {
+        if buckets == 0 {
+            return self.round(self.initial_buckets);
+        }
+        let grown = ceil(buckets as f64 * self.growth_factor);
+        self.round(grown.max(buckets + 1))
+    }
+
+    // bucket count to shrink to once removals leave `items` in a table of `buckets`, if the load
+    // has dropped below the shrink load. the table is halved until the load is back above it, so
+    // one removal halves it once and a bulk removal can halve it several times in one rehash, but
+    // never below the initial size
+    pub(crate) fn shrink(&self, items: usize, buckets: usize) -> Option<usize> {
+        let shrink_load = self.shrink_load?;
+        let floor = self.round(self.initial_buckets);
+
+        let mut target = buckets;
+        while target > floor && (items as f64) < target as f64 * shrink_load {
+            target = self.round(target / 2).max(floor);
+        }
+        (target < buckets).then_some(target)
+    }
+}
+
+// bucket for a hash in a power of two table of `buckets`. the top bits of the product depend on
+// every bit of the hash, so those are the ones kept. doubling the table only adds the next bit
+// below them, so bucket i of a table splits into buckets 2i and 2i + 1 of the one twice its size
+pub(crate) fn fibonacci_index(hash: u64, buckets: usize) -> usize {
+    // a one bucket table would need a shift by 64, which overflows
+    if buckets == 1 {
+        return 0;
+    }
+    (hash.wrapping_mul(FIBONACCI) >> (64 - buckets.trailing_zeros())) as usize
+}
+
+// f64::ceil needs std, sizes are never negative so a truncating cast and a bump will do
+fn ceil(x: f64) -> usize {
+    let truncated = x as usize;
+    if (truncated as f64) < x {
+        truncated.saturating_add(1)
+    } else {
+        truncated
+    }
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        GrowthPolicy::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashMap;
+    use std::hash::{BuildHasherDefault, Hasher};
+
+    // hashes integers to themselves, the sort of weak hasher people reach for to go fast
+    #[derive(Default)]
+    struct IdentityHasher(u64);
+
+    impl Hasher for IdentityHasher {
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = self.0.rotate_left(8) ^ byte as u64;
+            }
+        }
+
+        fn write_u64(&mut self, n: u64) {
+            self.0 = n;
+        }
+
+        fn finish(&self) -> u64 {
+            self.0
+        }
+    }
+
+    type Identity = BuildHasherDefault<IdentityHasher>;
+
+    fn longest_chain(policy: GrowthPolicy, keys: impl Iterator<Item = u64>) -> usize {
+        let mut map = HashMap::with_policy_and_hasher(policy, Identity::default());
+        for key in keys {
+            map.insert(key, ());
+        }
+        map.buckets.iter().map(|bucket| bucket.len()).max().unwrap()
+    }
+
+    #[test]
+    fn power_of_two_sizes() {
+        let mut map = HashMap::with_policy(GrowthPolicy::new().power_of_two());
+        let mut sizes = Vec::new();
+        for i in 0..1000 {
+            map.insert(i, i);
+            if sizes.last() != Some(&map.buckets.len()) {
+                sizes.push(map.buckets.len());
+            }
+        }
+        assert_eq!(sizes, [16, 32, 64, 128, 256, 512, 1024, 2048]);
+
+        let mut map: HashMap<u32, u32> =
+            HashMap::with_policy(GrowthPolicy::new().power_of_two().max_load(0.9));
+        map.reserve(1000);
+        assert!(map.buckets.len().is_power_of_two());
+        assert!(map.capacity() >= 1000);
+    }
+
+    #[test]
+    fn shrink_halves_down_to_the_initial_size() {
+        let policy = GrowthPolicy::new().shrink_below(0.25);
+        assert_eq!(policy.shrink(100, 320), None);
+        assert_eq!(policy.shrink(79, 320), Some(160));
+        // a bulk removal halves as often as it takes in one go
+        assert_eq!(policy.shrink(10, 320), Some(40));
+        assert_eq!(policy.shrink(0, 320), Some(10));
+        assert_eq!(policy.shrink(0, 10), None);
+        assert_eq!(GrowthPolicy::new().shrink(0, 320), None);
+
+        let pow2 = GrowthPolicy::new().power_of_two().shrink_below(0.25);
+        assert_eq!(pow2.shrink(0, 1024), Some(16));
+    }
+
+    #[test]
+    #[should_panic(expected = "below half the max load")]
+    fn shrink_load_leaves_room_to_grow() {
+        let _: HashMap<u32, u32> =
+            HashMap::with_policy(GrowthPolicy::new().shrink_below(0.25).max_load(0.5));
+    }
+
+    #[test]
+    fn builder_order_doesnt_matter() {
+        let a = GrowthPolicy::new().shrink_below(0.4).max_load(0.9);
+        let b = GrowthPolicy::new().max_load(0.9).shrink_below(0.4);
+        assert_eq!(a, b);
+        let _: HashMap<u32, u32> = HashMap::with_policy(a);
+    }
+
+    #[test]
+    fn mixing_spreads_weak_hashes() {
+        let modulo = GrowthPolicy::new();
+        let fibonacci = GrowthPolicy::new().power_of_two();
+
+        // sequential keys are the easy case for both schemes
+        assert!(longest_chain(fibonacci, 0..10_000) <= 8);
+        assert!(longest_chain(modulo, 0..10_000) <= 8);
+
+        // keys that only differ above the low bits: a plain mask would put them all in bucket 0,
+        // and modulo by 10 * 2^k still only reaches a handful of buckets
+        let strided = || (0..10_000).map(|i| i << 16);
+        let mixed = longest_chain(fibonacci, strided());
+        assert!(mixed <= 8, "fibonacci: {mixed}");
+        assert!(longest_chain(modulo, strided()) > 10 * mixed);
+
+        // same again with keys that only differ in the top 16 bits, which a mix that keeps
+        // the middle of the product would throw away
+        let mixed = longest_chain(fibonacci, (0..10_000).map(|i| i << 48));
+        assert!(mixed <= 8, "fibonacci, high bits: {mixed}");
+        let mixed = longest_chain(fibonacci, (0..10_000).map(|i| i << 40));
+        assert!(mixed <= 8, "fibonacci, bits 40 and up: {mixed}");
+    }
+
+    #[test]
+    fn single_bucket_power_of_two() {
+        let policy = GrowthPolicy::new().power_of_two();
+        assert_eq!(policy.index(u64::MAX, 1), 0);
+        // an odd multiplier keeps the top bit of 2^63 in place
+        assert_eq!(policy.index(1 << 63, 2), 1);
+        assert_eq!(policy.index(0, 2), 0);
+
+        let mut map = HashMap::with_policy(policy.initial_buckets(1).max_load(4.0));
+        for i in 0..3 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.buckets.len(), 1);
+        assert_eq!(map.get(&2), Some(&2));
+    }
+}
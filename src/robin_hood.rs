@@ -0,0 +1,311 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::DefaultHashBuilder;
+
+const INITIAL_NSLOTS: usize = 16;
+
+struct Bucket<K, V> {
+    // how many slots past its home slot this pair sits
+    dist: usize,
+    key: K,
+    value: V,
+}
+
+// same API as HashMap, backed by a linearly probed table using Robin Hood hashing: an insert
+// that has travelled further from home than the pair in its way takes that slot and keeps
+// pushing the displaced pair along. every pair ends up roughly as far from home as the others,
+// which keeps probe lengths short and predictable even at a load factor of 9/10.
+// removals shift the following pairs back one slot instead of leaving tombstones.
+pub struct RobinHoodHashMap<K, V, S = DefaultHashBuilder> {
+    slots: Vec<Option<Bucket<K, V>>>,
+    items: usize,
+    hash_builder: S,
+}
+
+impl<K, V> RobinHoodHashMap<K, V, DefaultHashBuilder> {
+    pub fn new() -> Self {
+        RobinHoodHashMap::with_hasher(DefaultHashBuilder::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        RobinHoodHashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+// number of slots needed to hold `capacity` items under the 9/10 load factor
+fn slots_for(capacity: usize) -> usize {
+    if capacity == 0 {
+        return 0;
+    }
+    let nslots = capacity.checked_mul(10).expect("capacity overflow").div_ceil(9);
+    nslots.next_power_of_two().max(INITIAL_NSLOTS)
+}
+
+fn empty_slots<K, V>(nslots: usize) -> Vec<Option<Bucket<K, V>>> {
+    let mut slots = Vec::with_capacity(nslots);
+    slots.extend((0..nslots).map(|_| None));
+    slots
+}
+
+impl<K, V, S> RobinHoodHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        RobinHoodHashMap {
+            slots: Vec::new(),
+            items: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = RobinHoodHashMap::with_hasher(hash_builder);
+        map.slots = empty_slots(slots_for(capacity));
+        map
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    // number of items the map can hold before the next rehash
+    pub fn capacity(&self) -> usize {
+        self.slots.len() * 9 / 10
+    }
+
+    // iterates over the pairs in slot order, which is arbitrary
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|bucket| (&bucket.key, &bucket.value))
+    }
+}
+
+impl<K, V, S: Default> Default for RobinHoodHashMap<K, V, S> {
+    fn default() -> Self {
+        RobinHoodHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> RobinHoodHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // the slot the key would sit in if nothing else was in the way
+    fn home<Q>(&self, key: &Q) -> usize
+    where
+        Q: Hash + ?Sized,
+    {
+        self.hash_builder.hash_one(key) as usize & (self.slots.len() - 1)
+    }
+
+    // index of the slot holding the key, if present
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        let mask = self.slots.len() - 1;
+        let mut index = self.home(key);
+        for dist in 0.. {
+            // a pair closer to home than we are now means the key would have displaced it
+            let bucket = self.slots[index].as_ref()?;
+            if bucket.dist < dist {
+                return None;
+            }
+            if bucket.key.borrow() == key {
+                return Some(index);
+            }
+            index = (index + 1) & mask;
+        }
+        unreachable!()
+    }
+
+    // puts a key that is known to be missing into the table, displacing richer pairs on the way
+    fn place(&mut self, key: K, value: V) {
+        let mask = self.slots.len() - 1;
+        let mut index = self.home(&key);
+        let mut carried = Bucket {
+            dist: 0,
+            key,
+            value,
+        };
+
+        loop {
+            match &mut self.slots[index] {
+                slot @ None => {
+                    *slot = Some(carried);
+                    return;
+                }
+                Some(bucket) => {
+                    if bucket.dist < carried.dist {
+                        mem::swap(bucket, &mut carried);
+                    }
+                }
+            }
+            index = (index + 1) & mask;
+            carried.dist += 1;
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(index) = self.find(&key) {
+            let bucket = self.slots[index].as_mut().unwrap();
+            return Some(mem::replace(&mut bucket.value, value));
+        }
+
+        if (self.items + 1) * 10 > self.slots.len() * 9 {
+            self.resize();
+        }
+        self.place(key, value);
+        self.items += 1;
+        None
+    }
+
+    fn resize(&mut self) {
+        let target_size = match self.slots.len() {
+            0 => INITIAL_NSLOTS,
+            n => 2 * n,
+        };
+
+        let old_slots = mem::replace(&mut self.slots, empty_slots(target_size));
+        for bucket in old_slots.into_iter().flatten() {
+            self.place(bucket.key, bucket.value);
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.slots[index].as_ref().map(|bucket| &bucket.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.slots[index].as_mut().map(|bucket| &mut bucket.value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    // takes the pair out, then shifts the pairs after it back a slot until one is already home
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // find fails on a table with no slots, so the mask below never underflows
+        let mut index = self.find(key)?;
+        let mask = self.slots.len() - 1;
+        let removed = self.slots[index].take()?;
+        self.items -= 1;
+
+        loop {
+            let next = (index + 1) & mask;
+            match self.slots[next].take() {
+                Some(mut bucket) if bucket.dist > 0 => {
+                    bucket.dist -= 1;
+                    self.slots[index] = Some(bucket);
+                    index = next;
+                }
+                bucket => {
+                    self.slots[next] = bucket;
+                    break;
+                }
+            }
+        }
+
+        Some(removed.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedState;
+
+    // checks that every pair's recorded distance matches where it actually sits
+    fn assert_distances<K: Hash + Eq, V, S: BuildHasher>(map: &RobinHoodHashMap<K, V, S>) {
+        let mask = map.slots.len() - 1;
+        for (index, slot) in map.slots.iter().enumerate() {
+            if let Some(bucket) = slot {
+                assert_eq!((map.home(&bucket.key) + bucket.dist) & mask, index);
+            }
+        }
+    }
+
+    #[test]
+    fn remove_from_an_empty_table() {
+        let mut map: RobinHoodHashMap<u32, u32> = RobinHoodHashMap::new();
+        assert_eq!(map.remove(&1), None);
+        let mut map: RobinHoodHashMap<u32, u32> = RobinHoodHashMap::with_capacity(0);
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.capacity(), 0);
+    }
+
+    #[test]
+    fn backward_shift_keeps_keys_reachable() {
+        let mut map = RobinHoodHashMap::with_hasher(FixedState::with_seed(3));
+        for i in 0..5_000u32 {
+            map.insert(i, i);
+        }
+        for i in (0..5_000u32).filter(|i| i % 3 == 0) {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+        assert_distances(&map);
+
+        for i in 0..5_000u32 {
+            assert_eq!(map.get(&i), (i % 3 != 0).then_some(&i));
+        }
+        assert_eq!(map.iter().count(), map.len());
+    }
+
+    #[test]
+    fn probe_lengths_stay_short_at_high_load() {
+        let mut map = RobinHoodHashMap::with_hasher(FixedState::with_seed(5));
+        let nslots = 1 << 14;
+        let items = nslots * 9 / 10;
+        for i in 0..items as u64 {
+            map.insert(i, ());
+        }
+        assert_eq!(map.slots.len(), nslots);
+        assert_distances(&map);
+
+        let dists: Vec<usize> = map.slots.iter().flatten().map(|b| b.dist).collect();
+        let mean = dists.iter().sum::<usize>() as f64 / dists.len() as f64;
+        let variance =
+            dists.iter().map(|&d| (d as f64 - mean).powi(2)).sum::<f64>() / dists.len() as f64;
+        let longest = dists.iter().max().copied().unwrap();
+
+        assert!(mean < 6.0, "mean probe length {mean}");
+        assert!(variance < 40.0, "probe length variance {variance}");
+        assert!(longest < 64, "longest probe {longest}");
+    }
+}
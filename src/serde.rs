@@ -0,0 +1,251 @@
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+
+use serde::Deserialize;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::{Allocator, DefaultHashBuilder, Entry, HashMap, HashSet};
+
+// what deserializing a map does when the input has the same key twice. JSON and most other
+// formats don't rule that out, and config files are a common place for it to slip in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    // keep the value that comes last, like std's HashMap and serde_json's own maps do
+    #[default]
+    LastWins,
+    // fail with an error naming the problem
+    Error,
+}
+
+// caps a size hint before it is used to pre-size a table. the hint comes from the input, so
+// without a cap a few bytes claiming a billion entries would allocate for all of them up front
+fn cautious<T>(hint: Option<usize>) -> usize {
+    const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+    let element = mem::size_of::<T>().max(1);
+    hint.unwrap_or(0).min(MAX_PREALLOC_BYTES / element)
+}
+
+// maps serialize as maps and sets as sequences, in iteration order
+impl<K, V, S, A> Serialize for HashMap<K, V, S, A>
+where
+    K: Serialize,
+    V: Serialize,
+    A: Allocator,
+{
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        serializer.collect_map(self)
+    }
+}
+
+impl<T, S> Serialize for HashSet<T, S>
+where
+    T: Serialize,
+{
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+// deserializes a HashMap with a chosen duplicate key policy and hasher, for when the plain
+// Deserialize impl (last wins, default hasher) isn't what's wanted:
+//
+//     let seed = HashMapSeed::new(DuplicateKeys::Error);
+//     let map: HashMap<String, u32> = seed.deserialize(&mut deserializer)?;
+pub struct HashMapSeed<K, V, S = DefaultHashBuilder> {
+    duplicates: DuplicateKeys,
+    hash_builder: S,
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> HashMapSeed<K, V, DefaultHashBuilder> {
+    pub fn new(duplicates: DuplicateKeys) -> Self {
+        HashMapSeed::with_hasher(duplicates, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> HashMapSeed<K, V, S> {
+    pub fn with_hasher(duplicates: DuplicateKeys, hash_builder: S) -> Self {
+        HashMapSeed {
+            duplicates,
+            hash_builder,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, K, V, S> DeserializeSeed<'de> for HashMapSeed<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher,
+{
+    type Value = HashMap<K, V, S>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, K, V, S> Visitor<'de> for HashMapSeed<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher,
+{
+    type Value = HashMap<K, V, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let capacity = cautious::<(K, V)>(access.size_hint());
+        let mut map = HashMap::with_capacity_and_hasher(capacity, self.hash_builder);
+
+        while let Some((key, value)) = access.next_entry()? {
+            match (map.entry(key), self.duplicates) {
+                (Entry::Occupied(mut entry), DuplicateKeys::LastWins) => {
+                    entry.insert(value);
+                }
+                (Entry::Occupied(_), DuplicateKeys::Error) => {
+                    return Err(de::Error::custom("duplicate key in map"));
+                }
+                (Entry::Vacant(entry), _) => {
+                    entry.insert(value);
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for HashMap<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HashMapSeed::with_hasher(DuplicateKeys::LastWins, S::default()).deserialize(deserializer)
+    }
+}
+
+// rejects maps with repeated keys, for use on a field:
+//
+//     #[serde(deserialize_with = "hashmap::deserialize_unique_keys")]
+//     limits: HashMap<String, u32>,
+pub fn deserialize_unique_keys<'de, D, K, V, S>(
+    deserializer: D,
+) -> Result<HashMap<K, V, S>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    HashMapSeed::with_hasher(DuplicateKeys::Error, S::default()).deserialize(deserializer)
+}
+
+struct HashSetVisitor<T, S> {
+    marker: PhantomData<fn() -> HashSet<T, S>>,
+}
+
+impl<'de, T, S> Visitor<'de> for HashSetVisitor<T, S>
+where
+    T: Deserialize<'de> + Hash + Eq,
+    S: BuildHasher + Default,
+{
+    type Value = HashSet<T, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    // repeated values collapse into one, the same as inserting them one by one
+    fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let capacity = cautious::<T>(access.size_hint());
+        let mut set = HashSet::with_capacity_and_hasher(capacity, S::default());
+        while let Some(value) = access.next_element()? {
+            set.insert(value);
+        }
+        Ok(set)
+    }
+}
+
+impl<'de, T, S> Deserialize<'de> for HashSet<T, S>
+where
+    T: Deserialize<'de> + Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(HashSetVisitor {
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedState;
+    use serde::de::value::{Error, MapDeserializer};
+
+    #[test]
+    fn json_round_trip() {
+        let map = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        let json = serde_json::to_string(&map).unwrap();
+        let back: HashMap<String, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, map);
+
+        let mut set = HashSet::new();
+        for i in 0..10u32 {
+            set.insert(i);
+        }
+        let json = serde_json::to_string(&set).unwrap();
+        let back: HashSet<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 10);
+        assert!((0..10).all(|i| back.contains(&i)));
+
+        let empty: HashMap<u32, u32> = serde_json::from_str("{}").unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let json = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let map: HashMap<String, u32> = serde_json::from_str(json).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"], 3);
+
+        let seed = HashMapSeed::<String, u32>::new(DuplicateKeys::Error);
+        let err = seed
+            .deserialize(&mut serde_json::Deserializer::from_str(json))
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate key"), "{err}");
+
+        let unique = |json| {
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            deserialize_unique_keys::<_, String, u32, DefaultHashBuilder>(&mut deserializer)
+        };
+        assert_eq!(unique(r#"{"a": 1, "b": 2}"#).unwrap()["b"], 2);
+        assert!(unique(json).is_err());
+    }
+
+    #[test]
+    fn pre_sizes_from_size_hint() {
+        let entries = MapDeserializer::<_, Error>::new((0..1000u32).map(|i| (i, i)));
+        let map: HashMap<u32, u32, FixedState> = HashMap::deserialize(entries).unwrap();
+        assert_eq!(map.len(), 1000);
+        assert_eq!(
+            map.buckets.len(),
+            HashMap::<u32, u32>::with_capacity(1000).buckets.len()
+        );
+
+        // a hint that claims far more entries than the input holds is capped
+        assert_eq!(cautious::<(u64, u64)>(Some(usize::MAX)), 65536);
+        assert_eq!(cautious::<()>(None), 0);
+    }
+}
@@ -0,0 +1,504 @@
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::iter::{Chain, FusedIterator};
+use core::mem;
+use core::ops::{BitAnd, BitOr, BitXor, Sub};
+
+use crate::{DefaultHashBuilder, HashMap, IntoKeys, Keys};
+
+// a set is a map whose values carry nothing, so it shares the bucket table, hashing and growth
+pub struct HashSet<T, S = DefaultHashBuilder> {
+    map: HashMap<T, (), S>,
+}
+
+impl<T> HashSet<T, DefaultHashBuilder> {
+    pub fn new() -> Self {
+        HashSet {
+            map: HashMap::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashSet {
+            map: HashMap::with_capacity(capacity),
+        }
+    }
+}
+
+impl<T, S> HashSet<T, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashSet {
+            map: HashMap::with_hasher(hash_builder),
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        HashSet {
+            map: HashMap::with_capacity_and_hasher(capacity, hash_builder),
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // number of values the set can hold before the next rehash
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    // iterates over the values in arbitrary order
+    pub fn iter(&self) -> SetIter<'_, T> {
+        SetIter {
+            inner: self.map.keys(),
+        }
+    }
+}
+
+impl<T, S: Default> Default for HashSet<T, S> {
+    fn default() -> Self {
+        HashSet::with_hasher(S::default())
+    }
+}
+
+impl<T, S> HashSet<T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    // adds the value, returns false if it was already there (the stored value is kept)
+    pub fn insert(&mut self, value: T) -> bool {
+        self.map.insert(value, ()).is_none()
+    }
+
+    // adds the value, swapping out and returning an equal value that was already stored
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        match self.map.find(&value) {
+            Some((bucket, index)) => {
+                Some(mem::replace(&mut self.map.buckets[bucket][index].key, value))
+            }
+            None => {
+                self.map.insert(value, ());
+                None
+            }
+        }
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(value)
+    }
+
+    // the stored value equal to the given one, handy when equal values can still differ
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (bucket, index) = self.map.find(value)?;
+        Some(&self.map.buckets[bucket][index].key)
+    }
+
+    // returns whether the value was present
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    // removes and returns the stored value equal to the given one
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove_entry(value).map(|(value, _)| value)
+    }
+
+    // values in self or other, each once
+    pub fn union<'a>(&'a self, other: &'a HashSet<T, S>) -> Union<'a, T, S> {
+        Union {
+            inner: self.iter().chain(other.difference(self)),
+        }
+    }
+
+    // values in both self and other. walks the smaller set and looks up in the bigger one
+    pub fn intersection<'a>(&'a self, other: &'a HashSet<T, S>) -> Intersection<'a, T, S> {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Intersection {
+            iter: small.iter(),
+            other: large,
+        }
+    }
+
+    // values in self that are not in other
+    pub fn difference<'a>(&'a self, other: &'a HashSet<T, S>) -> Difference<'a, T, S> {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    // values in exactly one of self and other
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a HashSet<T, S>,
+    ) -> SymmetricDifference<'a, T, S> {
+        SymmetricDifference {
+            inner: self.difference(other).chain(other.difference(self)),
+        }
+    }
+
+    pub fn is_disjoint(&self, other: &HashSet<T, S>) -> bool {
+        self.intersection(other).next().is_none()
+    }
+
+    // every value of self is also in other
+    pub fn is_subset(&self, other: &HashSet<T, S>) -> bool {
+        self.len() <= other.len() && self.iter().all(|value| other.contains(value))
+    }
+
+    pub fn is_superset(&self, other: &HashSet<T, S>) -> bool {
+        other.is_subset(self)
+    }
+}
+
+pub struct SetIter<'a, T> {
+    inner: Keys<'a, T, ()>,
+}
+
+pub struct SetIntoIter<T> {
+    inner: IntoKeys<T, ()>,
+}
+
+pub struct Union<'a, T, S> {
+    inner: Chain<SetIter<'a, T>, Difference<'a, T, S>>,
+}
+
+pub struct Intersection<'a, T, S> {
+    iter: SetIter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+pub struct Difference<'a, T, S> {
+    iter: SetIter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+pub struct SymmetricDifference<'a, T, S> {
+    inner: Chain<Difference<'a, T, S>, Difference<'a, T, S>>,
+}
+
+impl<'a, T, S> IntoIterator for &'a HashSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = SetIter<'a, T>;
+
+    fn into_iter(self) -> SetIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T, S> IntoIterator for HashSet<T, S> {
+    type Item = T;
+    type IntoIter = SetIntoIter<T>;
+
+    fn into_iter(self) -> SetIntoIter<T> {
+        SetIntoIter {
+            inner: self.map.into_keys(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for SetIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> Iterator for SetIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T, S> Iterator for Union<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T, S> Iterator for Intersection<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let other = self.other;
+        self.iter.find(|value| other.contains(*value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<'a, T, S> Iterator for Difference<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let other = self.other;
+        self.iter.find(|value| !other.contains(*value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<'a, T, S> Iterator for SymmetricDifference<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for SetIter<'_, T> {}
+impl<T> ExactSizeIterator for SetIntoIter<T> {}
+impl<T> FusedIterator for SetIter<'_, T> {}
+impl<T> FusedIterator for SetIntoIter<T> {}
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for Union<'_, T, S> {}
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for Intersection<'_, T, S> {}
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for Difference<'_, T, S> {}
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for SymmetricDifference<'_, T, S> {}
+
+// the operators collect the lazy iterators above into a new set with a fresh hasher
+impl<T, S> BitOr<&HashSet<T, S>> for &HashSet<T, S>
+where
+    T: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn bitor(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        collect(self.union(other))
+    }
+}
+
+impl<T, S> BitAnd<&HashSet<T, S>> for &HashSet<T, S>
+where
+    T: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn bitand(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        collect(self.intersection(other))
+    }
+}
+
+impl<T, S> Sub<&HashSet<T, S>> for &HashSet<T, S>
+where
+    T: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn sub(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        collect(self.difference(other))
+    }
+}
+
+impl<T, S> BitXor<&HashSet<T, S>> for &HashSet<T, S>
+where
+    T: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn bitxor(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        collect(self.symmetric_difference(other))
+    }
+}
+
+fn collect<'a, T, S>(values: impl Iterator<Item = &'a T>) -> HashSet<T, S>
+where
+    T: Hash + Eq + Clone + 'a,
+    S: BuildHasher + Default,
+{
+    let mut set = HashSet::with_capacity_and_hasher(values.size_hint().0, S::default());
+    for value in values {
+        set.insert(value.clone());
+    }
+    set
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(values: &[u32]) -> HashSet<u32> {
+        let mut set = HashSet::new();
+        for &value in values {
+            set.insert(value);
+        }
+        set
+    }
+
+    fn sorted<'a>(values: impl Iterator<Item = &'a u32>) -> Vec<u32> {
+        let mut values: Vec<u32> = values.copied().collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn insert_contains_remove() {
+        let mut set = HashSet::new();
+        assert!(set.insert(String::from("abc")));
+        assert!(!set.insert(String::from("abc")));
+        assert!(set.contains("abc"));
+        assert_eq!(set.len(), 1);
+
+        assert!(set.remove("abc"));
+        assert!(!set.remove("abc"));
+        assert!(set.is_empty());
+    }
+
+    // equal by the first field only, so it can tell which of two equal values got stored
+    #[derive(Debug)]
+    struct Tagged(u32, &'static str);
+
+    impl PartialEq for Tagged {
+        fn eq(&self, other: &Tagged) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Tagged {}
+
+    impl Hash for Tagged {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+
+    #[test]
+    fn get_take_replace() {
+        let mut set = HashSet::new();
+        set.insert(Tagged(1, "first"));
+        assert!(!set.insert(Tagged(1, "second")));
+        assert_eq!(set.get(&Tagged(1, "")).unwrap().1, "first");
+
+        assert_eq!(set.replace(Tagged(1, "third")).unwrap().1, "first");
+        assert_eq!(set.get(&Tagged(1, "")).unwrap().1, "third");
+        assert!(set.replace(Tagged(2, "new")).is_none());
+
+        assert_eq!(set.take(&Tagged(1, "")).unwrap().1, "third");
+        assert_eq!(set.take(&Tagged(1, "")), None);
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn set_algebra() {
+        let a = set(&[1, 2, 3, 4]);
+        let b = set(&[3, 4, 5]);
+
+        assert_eq!(sorted(a.union(&b)), [1, 2, 3, 4, 5]);
+        assert_eq!(sorted(a.intersection(&b)), [3, 4]);
+        assert_eq!(sorted(b.intersection(&a)), [3, 4]);
+        assert_eq!(sorted(a.difference(&b)), [1, 2]);
+        assert_eq!(sorted(b.difference(&a)), [5]);
+        assert_eq!(sorted(a.symmetric_difference(&b)), [1, 2, 5]);
+    }
+
+    #[test]
+    fn set_relations() {
+        let a = set(&[1, 2, 3]);
+        let b = set(&[1, 2]);
+        let c = set(&[7]);
+        let empty = set(&[]);
+
+        assert!(b.is_subset(&a));
+        assert!(!a.is_subset(&b));
+        assert!(a.is_superset(&b));
+        assert!(empty.is_subset(&a));
+        assert!(a.is_disjoint(&c));
+        assert!(!a.is_disjoint(&b));
+        assert!(empty.is_disjoint(&empty));
+    }
+
+    #[test]
+    fn operators() {
+        let a = set(&[1, 2, 3]);
+        let b = set(&[2, 3, 4]);
+
+        assert_eq!(sorted((&a | &b).iter()), [1, 2, 3, 4]);
+        assert_eq!(sorted((&a & &b).iter()), [2, 3]);
+        assert_eq!(sorted((&a - &b).iter()), [1]);
+        assert_eq!(sorted((&a ^ &b).iter()), [1, 4]);
+    }
+
+    #[test]
+    fn iterators() {
+        let a = set(&[1, 2, 3]);
+        assert_eq!(a.iter().len(), 3);
+        assert_eq!(sorted((&a).into_iter()), [1, 2, 3]);
+
+        let mut owned: Vec<u32> = a.into_iter().collect();
+        owned.sort();
+        assert_eq!(owned, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,909 @@
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::iter::{self, FusedIterator};
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use core::slice;
+
+use crate::{DefaultHashBuilder, HashMap, IntoIter, Iter, IterMut, Slot};
+
+// up to N pairs stored in place, in insertion order until something is removed
+struct Inline<K, V, const N: usize> {
+    pairs: [MaybeUninit<(K, V)>; N],
+    len: usize,
+}
+
+impl<K, V, const N: usize> Inline<K, V, N> {
+    const fn new() -> Self {
+        Inline {
+            pairs: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+
+    fn as_slice(&self) -> &[(K, V)] {
+        // SAFETY: the first len pairs are initialised
+        unsafe { slice::from_raw_parts(self.pairs.as_ptr().cast(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [(K, V)] {
+        // SAFETY: as for as_slice, and self is borrowed mutably
+        unsafe { slice::from_raw_parts_mut(self.pairs.as_mut_ptr().cast(), self.len) }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    // panics when full, callers check is_full first
+    fn push(&mut self, pair: (K, V)) {
+        self.pairs[self.len].write(pair);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: the pair at the old last index is initialised and no longer counted
+        Some(unsafe { self.pairs[self.len].assume_init_read() })
+    }
+
+    // removes the pair at `index` and moves the last one into its place
+    fn swap_remove(&mut self, index: usize) -> (K, V) {
+        let last = self.len - 1;
+        self.as_mut_slice().swap(index, last);
+        self.pop().unwrap()
+    }
+
+    fn clear(&mut self) {
+        let pairs: *mut [(K, V)] = self.as_mut_slice();
+        // the length goes first, so a panicking destructor leaks the rest instead of dropping
+        // them twice
+        self.len = 0;
+        // SAFETY: the pairs were initialised and are no longer counted
+        unsafe { ptr::drop_in_place(pairs) };
+    }
+}
+
+impl<K: Clone, V: Clone, const N: usize> Clone for Inline<K, V, N> {
+    fn clone(&self) -> Self {
+        let mut inline = Inline::new();
+        for pair in self.as_slice() {
+            inline.push(pair.clone());
+        }
+        inline
+    }
+}
+
+impl<K, V, const N: usize> Drop for Inline<K, V, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// same API as HashMap, for maps that usually stay tiny. the first N pairs are kept inline and
+// found by a linear scan with K::eq, so a small map never allocates or hashes a key. the pair
+// that doesn't fit moves everything into a HashMap, and removals that bring a spilled map down
+// to N / 2 pairs move them back inline and free the table. the gap between the two thresholds
+// keeps a map that hovers around N from moving back and forth
+pub struct SmallHashMap<K, V, const N: usize, S = DefaultHashBuilder> {
+    // holds every pair while the map is inline, and nothing once it has spilled
+    inline: Inline<K, V, N>,
+    // holds every pair once the map has spilled. while the map is inline it has no buckets and
+    // doesn't allocate, it only keeps the hasher
+    table: HashMap<K, V, S>,
+}
+
+impl<K, V, const N: usize> SmallHashMap<K, V, N, DefaultHashBuilder> {
+    pub fn new() -> Self {
+        SmallHashMap::with_hasher(DefaultHashBuilder::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SmallHashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, const N: usize, S> SmallHashMap<K, V, N, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        SmallHashMap {
+            inline: Inline::new(),
+            table: HashMap::with_hasher(hash_builder),
+        }
+    }
+
+    // a capacity above N starts the map out spilled, with a table that fits it
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let capacity = if capacity > N { capacity } else { 0 };
+        SmallHashMap {
+            inline: Inline::new(),
+            table: HashMap::with_capacity_and_hasher(capacity, hash_builder),
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        self.table.hasher()
+    }
+
+    // true while the pairs are stored inline rather than in a hashed table
+    pub fn is_inline(&self) -> bool {
+        self.table.capacity() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.inline.len + self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // number of items the map can hold before it spills or the table rehashes
+    pub fn capacity(&self) -> usize {
+        if self.is_inline() {
+            N
+        } else {
+            self.table.capacity()
+        }
+    }
+
+    // iterates over the pairs in arbitrary order
+    pub fn iter(&self) -> SmallIter<'_, K, V> {
+        SmallIter {
+            inline: self.inline.as_slice().iter(),
+            table: self.table.iter(),
+        }
+    }
+
+    // same as iter but the values can be changed in place
+    pub fn iter_mut(&mut self) -> SmallIterMut<'_, K, V> {
+        SmallIterMut {
+            inline: self.inline.as_mut_slice().iter_mut(),
+            table: self.table.iter_mut(),
+        }
+    }
+
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl ExactSizeIterator<Item = &mut V> {
+        self.iter_mut().map(|(_, value)| value)
+    }
+
+    // takes every pair out of the map. like HashMap::drain, a spilled map keeps its table for
+    // reuse, clear is what frees it
+    pub fn drain(&mut self) -> impl Iterator<Item = (K, V)> + '_ {
+        let mut inline = mem::replace(&mut self.inline, Inline::new());
+        iter::from_fn(move || inline.pop()).chain(self.table.drain())
+    }
+}
+
+impl<K, V, const N: usize, S: Default> Default for SmallHashMap<K, V, N, S> {
+    fn default() -> Self {
+        SmallHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, const N: usize, S> SmallHashMap<K, V, N, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn position<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.inline
+            .as_slice()
+            .iter()
+            .position(|(k, _)| k.borrow() == key)
+    }
+
+    // moves every inline pair into the table, with room for one more
+    fn spill(&mut self) {
+        self.table.reserve(N + 1);
+        while let Some((key, value)) = self.inline.pop() {
+            self.table.insert(key, value);
+        }
+    }
+
+    // moves the pairs back inline once a spilled map is down to N / 2, and frees the table
+    fn unspill_if_small(&mut self) {
+        if self.is_inline() || self.table.len() > N / 2 {
+            return;
+        }
+        for pair in self.table.drain() {
+            self.inline.push(pair);
+        }
+        self.table.shrink_to_fit();
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.is_inline() {
+            if let Some(pos) = self.position(&key) {
+                let pair = &mut self.inline.as_mut_slice()[pos];
+                return Some(mem::replace(&mut pair.1, value));
+            }
+            if !self.inline.is_full() {
+                self.inline.push((key, value));
+                return None;
+            }
+            self.spill();
+        }
+        self.table.insert(key, value)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_inline() {
+            let pos = self.position(key)?;
+            Some(&self.inline.as_slice()[pos].1)
+        } else {
+            self.table.get(key)
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_inline() {
+            let pos = self.position(key)?;
+            Some(&mut self.inline.as_mut_slice()[pos].1)
+        } else {
+            self.table.get_mut(key)
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    // get the stored key along with the value
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_inline() {
+            let (key, value) = &self.inline.as_slice()[self.position(key)?];
+            Some((key, value))
+        } else {
+            self.table.get_key_value(key)
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    // same as remove, but also hands back the key that was stored
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_inline() {
+            let pos = self.position(key)?;
+            return Some(self.inline.swap_remove(pos));
+        }
+        let pair = self.table.remove_entry(key)?;
+        self.unspill_if_small();
+        Some(pair)
+    }
+
+    // keeps only the pairs for which `f` returns true, moving back inline if few enough are left
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        if self.is_inline() {
+            let mut index = 0;
+            while index < self.inline.len {
+                let (key, value) = &mut self.inline.as_mut_slice()[index];
+                if f(key, value) {
+                    index += 1;
+                } else {
+                    // the last pair moves into this index and gets looked at next
+                    self.inline.swap_remove(index);
+                }
+            }
+        } else {
+            self.table.retain(f);
+            self.unspill_if_small();
+        }
+    }
+
+    // gets the entry for the key. an inline map that is full and doesn't hold the key spills
+    // first, so a vacant entry always has room
+    pub fn entry(&mut self, key: K) -> SmallEntry<'_, K, V, N, S> {
+        if self.is_inline() {
+            if let Some(pos) = self.position(&key) {
+                return SmallEntry::Occupied(SmallOccupiedEntry {
+                    map: self,
+                    at: Location::Inline(pos),
+                });
+            }
+            if !self.inline.is_full() {
+                return SmallEntry::Vacant(SmallVacantEntry {
+                    map: self,
+                    key,
+                    at: VacantLocation::Inline,
+                });
+            }
+            self.spill();
+        }
+
+        // the same steps as HashMap::entry, keeping hold of the whole map instead of one bucket
+        // so that removing through the entry can move the pairs back inline
+        if self.table.needs_resize() {
+            self.table.resize();
+        }
+        let hash = self.table.hash(&key);
+        let bucket = self.table.bucket(hash);
+        match self.table.position(bucket, hash, &key) {
+            Some(index) => SmallEntry::Occupied(SmallOccupiedEntry {
+                map: self,
+                at: Location::Table(bucket, index),
+            }),
+            None => SmallEntry::Vacant(SmallVacantEntry {
+                map: self,
+                key,
+                at: VacantLocation::Table(hash, bucket),
+            }),
+        }
+    }
+
+    // removes every pair and frees the table if the map had spilled
+    pub fn clear(&mut self) {
+        self.inline.clear();
+        self.table.clear();
+        self.table.shrink_to_fit();
+    }
+}
+
+// where an occupied entry's pair sits: an index into the inline pairs, or a bucket and position
+// in the table
+#[derive(Clone, Copy)]
+enum Location {
+    Inline(usize),
+    Table(usize, usize),
+}
+
+// where a vacant entry's pair will go: the end of the inline pairs, or the bucket the key hashed to
+#[derive(Clone, Copy)]
+enum VacantLocation {
+    Inline,
+    Table(u64, usize),
+}
+
+// a view into a single pair of a SmallHashMap, with the same methods as HashMap's Entry
+pub enum SmallEntry<'a, K, V, const N: usize, S = DefaultHashBuilder> {
+    Occupied(SmallOccupiedEntry<'a, K, V, N, S>),
+    Vacant(SmallVacantEntry<'a, K, V, N, S>),
+}
+
+pub struct SmallOccupiedEntry<'a, K, V, const N: usize, S = DefaultHashBuilder> {
+    map: &'a mut SmallHashMap<K, V, N, S>,
+    at: Location,
+}
+
+pub struct SmallVacantEntry<'a, K, V, const N: usize, S = DefaultHashBuilder> {
+    map: &'a mut SmallHashMap<K, V, N, S>,
+    key: K,
+    at: VacantLocation,
+}
+
+impl<'a, K, V, const N: usize, S> SmallEntry<'a, K, V, N, S> {
+    // returns the value for the key, inserting the default if the entry is vacant
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            SmallEntry::Occupied(entry) => entry.into_mut(),
+            SmallEntry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            SmallEntry::Occupied(entry) => entry.into_mut(),
+            SmallEntry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            SmallEntry::Occupied(entry) => entry.into_mut(),
+            SmallEntry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            SmallEntry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                SmallEntry::Occupied(entry)
+            }
+            SmallEntry::Vacant(entry) => SmallEntry::Vacant(entry),
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            SmallEntry::Occupied(entry) => entry.key(),
+            SmallEntry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<'a, K, V: Default, const N: usize, S> SmallEntry<'a, K, V, N, S> {
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K, V, const N: usize, S> SmallOccupiedEntry<'a, K, V, N, S> {
+    fn pair(&self) -> (&K, &V) {
+        match self.at {
+            Location::Inline(pos) => {
+                let (key, value) = &self.map.inline.as_slice()[pos];
+                (key, value)
+            }
+            Location::Table(bucket, index) => {
+                let slot = &self.map.table.buckets[bucket][index];
+                (&slot.key, &slot.value)
+            }
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        self.pair().0
+    }
+
+    pub fn get(&self) -> &V {
+        self.pair().1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        match self.at {
+            Location::Inline(pos) => &mut self.map.inline.as_mut_slice()[pos].1,
+            Location::Table(bucket, index) => &mut self.map.table.buckets[bucket][index].value,
+        }
+    }
+
+    // turns the entry into a reference to the value that lives as long as the map borrow
+    pub fn into_mut(self) -> &'a mut V {
+        match self.at {
+            Location::Inline(pos) => &mut self.map.inline.as_mut_slice()[pos].1,
+            Location::Table(bucket, index) => &mut self.map.table.buckets[bucket][index].value,
+        }
+    }
+
+    // replaces the value and returns the old one
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize, S: BuildHasher> SmallOccupiedEntry<'_, K, V, N, S> {
+    // takes the pair out of the map, moving back inline like remove does
+    pub fn remove_entry(self) -> (K, V) {
+        match self.at {
+            Location::Inline(pos) => self.map.inline.swap_remove(pos),
+            Location::Table(bucket, index) => {
+                let table = &mut self.map.table;
+                let slot = table.buckets[bucket].swap_remove(index);
+                table.items -= 1;
+                table.shrink_if_sparse();
+                self.map.unspill_if_small();
+                (slot.key, slot.value)
+            }
+        }
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+impl<'a, K, V, const N: usize, S> SmallVacantEntry<'a, K, V, N, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    // puts the value where the key belongs and returns a reference to it
+    pub fn insert(self, value: V) -> &'a mut V {
+        match self.at {
+            VacantLocation::Inline => {
+                let inline = &mut self.map.inline;
+                inline.push((self.key, value));
+                &mut inline.as_mut_slice().last_mut().unwrap().1
+            }
+            VacantLocation::Table(hash, bucket) => {
+                let table = &mut self.map.table;
+                table.items += 1;
+                let bucket = &mut table.buckets[bucket];
+                bucket.push(Slot {
+                    hash,
+                    key: self.key,
+                    value,
+                });
+                &mut bucket.last_mut().unwrap().value
+            }
+        }
+    }
+}
+
+// the inline pairs and then the table's. only one of the two is ever non-empty
+pub struct SmallIter<'a, K, V> {
+    inline: slice::Iter<'a, (K, V)>,
+    table: Iter<'a, K, V>,
+}
+
+pub struct SmallIterMut<'a, K, V> {
+    inline: slice::IterMut<'a, (K, V)>,
+    table: IterMut<'a, K, V>,
+}
+
+pub struct SmallIntoIter<K, V, const N: usize> {
+    inline: Inline<K, V, N>,
+    table: IntoIter<K, V>,
+}
+
+impl<'a, K, V> Iterator for SmallIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inline.next() {
+            Some((key, value)) => Some((key, value)),
+            None => self.table.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.inline.len() + self.table.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, K, V> Iterator for SmallIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inline.next() {
+            Some((key, value)) => Some((&*key, value)),
+            None => self.table.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.inline.len() + self.table.len();
+        (len, Some(len))
+    }
+}
+
+impl<K, V, const N: usize> Iterator for SmallIntoIter<K, V, N> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inline.pop().or_else(|| self.table.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.inline.len + self.table.len();
+        (len, Some(len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for SmallIter<'_, K, V> {}
+impl<K, V> ExactSizeIterator for SmallIterMut<'_, K, V> {}
+impl<K, V, const N: usize> ExactSizeIterator for SmallIntoIter<K, V, N> {}
+impl<K, V> FusedIterator for SmallIter<'_, K, V> {}
+impl<K, V> FusedIterator for SmallIterMut<'_, K, V> {}
+impl<K, V, const N: usize> FusedIterator for SmallIntoIter<K, V, N> {}
+
+impl<'a, K, V, const N: usize, S> IntoIterator for &'a SmallHashMap<K, V, N, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = SmallIter<'a, K, V>;
+
+    fn into_iter(self) -> SmallIter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, const N: usize, S> IntoIterator for &'a mut SmallHashMap<K, V, N, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = SmallIterMut<'a, K, V>;
+
+    fn into_iter(self) -> SmallIterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, const N: usize, S> IntoIterator for SmallHashMap<K, V, N, S> {
+    type Item = (K, V);
+    type IntoIter = SmallIntoIter<K, V, N>;
+
+    fn into_iter(self) -> SmallIntoIter<K, V, N> {
+        SmallIntoIter {
+            inline: self.inline,
+            table: self.table.into_iter(),
+        }
+    }
+}
+
+// a clone keeps the layout: an inline map clones inline, a spilled one clones its table
+impl<K: Clone, V: Clone, const N: usize, S: Clone> Clone for SmallHashMap<K, V, N, S> {
+    fn clone(&self) -> Self {
+        SmallHashMap {
+            inline: self.inline.clone(),
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, const N: usize, S> fmt::Debug for SmallHashMap<K, V, N, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// equal when they hold the same pairs, whether either of them has spilled or not
+impl<K, V, const N: usize, S> PartialEq for SmallHashMap<K, V, N, S>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key).is_some_and(|v| value == v))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, const N: usize, S: BuildHasher> Eq for SmallHashMap<K, V, N, S> {}
+
+impl<K, V, const N: usize, S> Extend<(K, V)> for SmallHashMap<K, V, N, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K, V, const N: usize, S> FromIterator<(K, V)> for SmallHashMap<K, V, N, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = SmallHashMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedState;
+    use std::cell::Cell;
+    use std::hash::Hasher;
+    use std::rc::Rc;
+
+    thread_local! {
+        static HASHES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(PartialEq, Eq)]
+    struct Counted(u32);
+
+    impl Hash for Counted {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            HASHES.with(|hashes| hashes.set(hashes.get() + 1));
+            self.0.hash(state);
+        }
+    }
+
+    #[test]
+    fn inline_maps_never_hash() {
+        let mut map = SmallHashMap::<_, _, 8>::new();
+        for i in 0..8 {
+            map.insert(Counted(i), i);
+        }
+        for i in 0..8 {
+            assert_eq!(map.get(&Counted(i)), Some(&i));
+        }
+        map.remove(&Counted(3));
+        assert!(map.is_inline());
+        assert_eq!(map.capacity(), 8);
+        assert_eq!(HASHES.with(Cell::get), 0);
+
+        // the ninth pair spills, hashing every key once on its way into the table
+        map.insert(Counted(3), 3);
+        map.insert(Counted(8), 8);
+        assert!(!map.is_inline());
+        assert_eq!(HASHES.with(Cell::get), 9);
+    }
+
+    #[test]
+    fn spills_and_comes_back() {
+        let mut map = SmallHashMap::<u32, u32, 4, _>::with_hasher(FixedState::with_seed(1));
+        for i in 0..100 {
+            map.insert(i, i * 2);
+        }
+        assert!(!map.is_inline());
+        assert_eq!(map.len(), 100);
+        for i in 0..100 {
+            assert_eq!(map.get(&i), Some(&(i * 2)));
+        }
+
+        // dropping to N stays spilled, so a map hovering around N doesn't move back and forth
+        for i in 4..100 {
+            assert_eq!(map.remove(&i), Some(i * 2));
+        }
+        assert!(!map.is_inline());
+        map.insert(4, 8);
+        map.remove(&4);
+        map.remove(&3);
+        assert!(!map.is_inline());
+
+        // at N / 2 the pairs move back inline and the table is freed
+        map.remove(&2);
+        assert!(map.is_inline());
+        assert_eq!(map.table.buckets.len(), 0);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&0), Some(&0));
+        assert_eq!(map.get(&1), Some(&2));
+        let mut pairs: Vec<_> = map.iter().collect();
+        pairs.sort();
+        assert_eq!(pairs, [(&0, &0), (&1, &2)]);
+    }
+
+    #[test]
+    fn with_capacity_starts_spilled() {
+        let map = SmallHashMap::<u32, u32, 8>::with_capacity(8);
+        assert!(map.is_inline());
+        let map = SmallHashMap::<u32, u32, 8>::with_capacity(100);
+        assert!(!map.is_inline());
+        assert!(map.capacity() >= 100);
+    }
+
+    #[test]
+    fn drops_every_pair_once() {
+        let value = Rc::new(());
+        let mut map = SmallHashMap::<u32, _, 4>::new();
+        for i in 0..4 {
+            map.insert(i, Rc::clone(&value));
+        }
+        map.remove(&0);
+        assert_eq!(Rc::strong_count(&value), 4);
+        map.clear();
+        assert_eq!(Rc::strong_count(&value), 1);
+
+        for i in 0..10 {
+            map.insert(i, Rc::clone(&value));
+        }
+        drop(map);
+        assert_eq!(Rc::strong_count(&value), 1);
+
+        // no inline room at all is just a HashMap
+        let mut empty = SmallHashMap::<u32, u32, 0>::new();
+        empty.insert(1, 1);
+        assert!(!empty.is_inline());
+        assert_eq!(empty.remove(&1), Some(1));
+        assert!(empty.is_inline());
+    }
+
+    #[test]
+    fn entry_spills_and_remove_comes_back() {
+        let mut map = SmallHashMap::<u32, u32, 4>::new();
+        for i in 0..4 {
+            *map.entry(i).or_insert(0) += i;
+        }
+        assert!(map.is_inline());
+        *map.entry(2).or_default() += 10;
+        assert_eq!(map.get(&2), Some(&12));
+
+        // a full inline map spills to make room for the vacant entry
+        assert_eq!(*map.entry(4).or_insert_with_key(|k| k * 2), 8);
+        assert!(!map.is_inline());
+        map.entry(4).and_modify(|v| *v += 1);
+        assert_eq!(map.get(&4), Some(&9));
+
+        // removing through an entry moves back inline like remove does
+        for i in [4, 3, 2] {
+            match map.entry(i) {
+                SmallEntry::Occupied(entry) => assert_eq!(entry.remove_entry().0, i),
+                SmallEntry::Vacant(_) => panic!("{i} should be in the map"),
+            }
+        }
+        assert!(map.is_inline());
+        assert_eq!(map.len(), 2);
+        match map.entry(7) {
+            SmallEntry::Occupied(_) => panic!("7 was never inserted"),
+            SmallEntry::Vacant(entry) => assert_eq!(entry.into_key(), 7),
+        }
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn iterators_and_retain_work_either_way() {
+        for n in [3, 20] {
+            let mut map: SmallHashMap<u32, u32, 4> = (0..n).map(|i| (i, i)).collect();
+            assert_eq!(map.is_inline(), n <= 4);
+            assert_eq!(map.iter().len(), n as usize);
+            for value in map.values_mut() {
+                *value *= 2;
+            }
+            for (key, value) in &mut map {
+                assert_eq!(*value, key * 2);
+            }
+            assert_eq!(map.keys().sum::<u32>(), (0..n).sum::<u32>());
+            assert_eq!(map.get_key_value(&1), Some((&1, &2)));
+            assert_eq!(map.remove_entry(&1), Some((1, 2)));
+
+            map.retain(|key, _| key % 2 == 0);
+            assert_eq!(map.is_inline(), n <= 4);
+            let mut kept: Vec<_> = map.clone().into_iter().collect();
+            kept.sort();
+            assert_eq!(
+                kept,
+                (0..n).step_by(2).map(|i| (i, i * 2)).collect::<Vec<_>>()
+            );
+            assert_eq!(map.drain().count(), kept.len());
+            assert!(map.is_empty());
+        }
+    }
+
+    #[test]
+    fn equality_ignores_whether_a_map_spilled() {
+        let mut spilled = SmallHashMap::<u32, u32, 4>::new();
+        spilled.extend((0..10).map(|i| (i, i)));
+        spilled.retain(|&key, _| key < 5);
+        // only a map down to half its inline room moves back, so this one stays spilled
+        assert!(!spilled.is_inline());
+        let mut inline = SmallHashMap::<u32, u32, 4>::new();
+        inline.extend((0..4).map(|i| (i, i)));
+        assert_ne!(spilled, inline);
+        spilled.remove(&4);
+        assert_eq!(spilled, inline);
+        assert_eq!(
+            format!("{:?}", SmallHashMap::<u32, u32, 4>::from_iter([(1, 2)])),
+            "{1: 2}"
+        );
+    }
+}
@@ -0,0 +1,513 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem::{self, MaybeUninit};
+
+use crate::DefaultHashBuilder;
+
+// number of control bytes matched at once, one SSE2 register
+const GROUP_WIDTH: usize = 16;
+const INITIAL_NSLOTS: usize = GROUP_WIDTH;
+
+// control byte values. a full slot stores the top 7 bits of its hash, so the high bit is clear;
+// empty and deleted both have the high bit set and differ in the low bits
+const EMPTY: u8 = 0b1111_1111;
+const DELETED: u8 = 0b1000_0000;
+
+// bit i set means byte i of the group matched
+#[derive(Clone, Copy)]
+struct BitMask(u16);
+
+impl BitMask {
+    fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    fn lowest(self) -> Option<usize> {
+        (self.0 != 0).then(|| self.0.trailing_zeros() as usize)
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let bit = self.lowest()?;
+        self.0 &= self.0 - 1;
+        Some(bit)
+    }
+}
+
+// SSE2 is part of the x86_64 baseline, so there is no runtime detection to do
+#[cfg(all(target_arch = "x86_64", any(test, not(swiss_generic))))]
+mod sse2 {
+    use super::{BitMask, GROUP_WIDTH};
+    use core::arch::x86_64::{
+        __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8,
+    };
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Group(__m128i);
+
+    impl Group {
+        pub(super) fn load(ctrl: &[u8; GROUP_WIDTH]) -> Group {
+            // SAFETY: the array is exactly 16 bytes and loadu has no alignment requirement
+            Group(unsafe { _mm_loadu_si128(ctrl.as_ptr().cast()) })
+        }
+
+        pub(super) fn match_byte(self, byte: u8) -> BitMask {
+            // SAFETY: sse2 is always available on x86_64
+            unsafe {
+                let eq = _mm_cmpeq_epi8(self.0, _mm_set1_epi8(byte as i8));
+                BitMask(_mm_movemask_epi8(eq) as u16)
+            }
+        }
+
+        pub(super) fn match_empty(self) -> BitMask {
+            self.match_byte(super::EMPTY)
+        }
+
+        // empty and deleted are the only control bytes with the high bit set
+        pub(super) fn match_empty_or_deleted(self) -> BitMask {
+            // SAFETY: sse2 is always available on x86_64
+            BitMask(unsafe { _mm_movemask_epi8(self.0) } as u16)
+        }
+    }
+}
+
+// portable fallback that treats the 16 control bytes as two u64 words ("SIMD within a register").
+// x86_64 only builds it for the tests, to check it against the SSE2 version, or when built with
+// `--cfg swiss_generic`, which makes the table use it so its insert, find and remove paths can be
+// tested on x86_64 too
+#[cfg(any(test, not(target_arch = "x86_64"), swiss_generic))]
+mod generic {
+    use super::{BitMask, GROUP_WIDTH};
+
+    const LO: u64 = 0x0101_0101_0101_0101;
+    const HI: u64 = 0x8080_8080_8080_8080;
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Group([u64; 2]);
+
+    // packs the high bit of each byte into the low 8 bits, like movemask does
+    fn movemask(word: u64) -> u16 {
+        (((word & HI) >> 7).wrapping_mul(0x0102_0408_1020_4080) >> 56) as u16
+    }
+
+    // sets the high bit of every byte that is zero, and of no other byte
+    fn zero_bytes(word: u64) -> u64 {
+        !(((word & !HI).wrapping_add(!HI)) | word | !HI)
+    }
+
+    impl Group {
+        pub(super) fn load(ctrl: &[u8; GROUP_WIDTH]) -> Group {
+            let (lo, hi) = ctrl.split_at(8);
+            Group([
+                u64::from_le_bytes(lo.try_into().unwrap()),
+                u64::from_le_bytes(hi.try_into().unwrap()),
+            ])
+        }
+
+        fn mask(self, f: impl Fn(u64) -> u64) -> BitMask {
+            BitMask(movemask(f(self.0[0])) | (movemask(f(self.0[1])) << 8))
+        }
+
+        pub(super) fn match_byte(self, byte: u8) -> BitMask {
+            self.mask(|word| zero_bytes(word ^ (LO * byte as u64)))
+        }
+
+        pub(super) fn match_empty(self) -> BitMask {
+            self.match_byte(super::EMPTY)
+        }
+
+        pub(super) fn match_empty_or_deleted(self) -> BitMask {
+            self.mask(|word| word)
+        }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(swiss_generic)))]
+use sse2::Group;
+#[cfg(any(not(target_arch = "x86_64"), swiss_generic))]
+use generic::Group;
+
+// splits a hash into the group to start probing at (h1) and the 7 bits kept in the control byte (h2)
+fn h1(hash: u64) -> usize {
+    (hash >> 7) as usize
+}
+
+fn h2(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+// same API as HashMap, laid out like Abseil's SwissTable. a separate array of control bytes holds
+// 7 bits of each slot's hash, and lookups compare a whole group of 16 control bytes against the
+// key's bits in one go, only touching the slots whose bits match. groups are probed
+// quadratically, and a lookup stops at the first group that still has an empty slot.
+pub struct SwissHashMap<K, V, S = DefaultHashBuilder> {
+    ctrl: Vec<u8>,
+    // slot i is initialised exactly when ctrl[i] holds a hash (high bit clear)
+    slots: Vec<MaybeUninit<(K, V)>>,
+    items: usize,
+    tombstones: usize,
+    hash_builder: S,
+}
+
+impl<K, V> SwissHashMap<K, V, DefaultHashBuilder> {
+    pub fn new() -> Self {
+        SwissHashMap::with_hasher(DefaultHashBuilder::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SwissHashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+// number of slots needed to hold `capacity` items under the 7/8 load factor
+fn slots_for(capacity: usize) -> usize {
+    if capacity == 0 {
+        return 0;
+    }
+    let nslots = capacity.checked_mul(8).expect("capacity overflow").div_ceil(7);
+    nslots.next_power_of_two().max(INITIAL_NSLOTS)
+}
+
+fn is_full(ctrl: u8) -> bool {
+    ctrl & 0x80 == 0
+}
+
+impl<K, V, S> SwissHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        SwissHashMap {
+            ctrl: Vec::new(),
+            slots: Vec::new(),
+            items: 0,
+            tombstones: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = SwissHashMap::with_hasher(hash_builder);
+        map.allocate(slots_for(capacity));
+        map
+    }
+
+    // replaces the table with an empty one of `nslots` slots, the caller deals with the old pairs
+    fn allocate(&mut self, nslots: usize) {
+        self.ctrl = vec![EMPTY; nslots];
+        self.slots = Vec::with_capacity(nslots);
+        self.slots.resize_with(nslots, MaybeUninit::uninit);
+        self.tombstones = 0;
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    // number of items the map can hold before the next rehash
+    pub fn capacity(&self) -> usize {
+        self.slots.len() * 7 / 8
+    }
+
+    // iterates over the pairs in slot order, which is arbitrary
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.ctrl
+            .iter()
+            .zip(&self.slots)
+            .filter(|(ctrl, _)| is_full(**ctrl))
+            // SAFETY: full control bytes mark initialised slots
+            .map(|(_, slot)| unsafe { slot.assume_init_ref() })
+            .map(|(key, value)| (key, value))
+    }
+
+    fn group(&self, group: usize) -> Group {
+        let start = group * GROUP_WIDTH;
+        Group::load(self.ctrl[start..start + GROUP_WIDTH].try_into().unwrap())
+    }
+
+    // group indices to visit for a hash, quadratically spaced so every group is visited once
+    fn probe(&self, hash: u64) -> impl Iterator<Item = usize> + use<K, V, S> {
+        let ngroups = self.ctrl.len() / GROUP_WIDTH;
+        let mask = ngroups - 1;
+        let home = h1(hash);
+        (0..ngroups).map(move |i| home.wrapping_add(i * (i + 1) / 2) & mask)
+    }
+
+    // first empty or deleted slot along the probe sequence
+    fn find_insert_slot(&self, hash: u64) -> usize {
+        for group in self.probe(hash) {
+            if let Some(bit) = self.group(group).match_empty_or_deleted().lowest() {
+                return group * GROUP_WIDTH + bit;
+            }
+        }
+        unreachable!("swiss table is full")
+    }
+
+    // SAFETY: the slot must be full
+    unsafe fn slot(&self, index: usize) -> &(K, V) {
+        unsafe { self.slots[index].assume_init_ref() }
+    }
+}
+
+impl<K, V, S: Default> Default for SwissHashMap<K, V, S> {
+    fn default() -> Self {
+        SwissHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> Drop for SwissHashMap<K, V, S> {
+    fn drop(&mut self) {
+        if mem::needs_drop::<(K, V)>() {
+            for (ctrl, slot) in self.ctrl.iter().zip(&mut self.slots) {
+                if is_full(*ctrl) {
+                    // SAFETY: full control bytes mark initialised slots, and this is the last use
+                    unsafe { slot.assume_init_drop() };
+                }
+            }
+        }
+    }
+}
+
+impl<K, V, S> SwissHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    // index of the slot holding the key, if present
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.ctrl.is_empty() {
+            return None;
+        }
+
+        for group in self.probe(hash) {
+            let ctrl = self.group(group);
+            for bit in ctrl.match_byte(h2(hash)) {
+                let index = group * GROUP_WIDTH + bit;
+                // SAFETY: the control byte matched a hash, so the slot is full
+                if unsafe { self.slot(index) }.0.borrow() == key {
+                    return Some(index);
+                }
+            }
+            // the key would have gone into this group's empty slot if it had got this far
+            if ctrl.match_empty().any() {
+                return None;
+            }
+        }
+        None
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.find(hash, &key) {
+            // SAFETY: find only returns full slots
+            let slot = unsafe { self.slots[index].assume_init_mut() };
+            return Some(mem::replace(&mut slot.1, value));
+        }
+
+        if (self.items + self.tombstones + 1) * 8 > self.slots.len() * 7 {
+            self.resize();
+        }
+
+        let index = self.find_insert_slot(hash);
+        if self.ctrl[index] == DELETED {
+            self.tombstones -= 1;
+        }
+        self.ctrl[index] = h2(hash);
+        self.slots[index].write((key, value));
+        self.items += 1;
+        None
+    }
+
+    // grows the table, or only clears the tombstones if the live items fit comfortably
+    fn resize(&mut self) {
+        let target_size = match self.slots.len() {
+            0 => INITIAL_NSLOTS,
+            n if self.items * 2 < n => n,
+            n => 2 * n,
+        };
+
+        let old_ctrl = mem::take(&mut self.ctrl);
+        let old_slots = mem::take(&mut self.slots);
+        self.allocate(target_size);
+
+        for (ctrl, slot) in old_ctrl.into_iter().zip(old_slots) {
+            if is_full(ctrl) {
+                // SAFETY: full control bytes mark initialised slots, and old_slots won't drop it
+                let (key, value) = unsafe { slot.assume_init() };
+                let hash = self.hash_builder.hash_one(&key);
+                let index = self.find_insert_slot(hash);
+                self.ctrl[index] = h2(hash);
+                self.slots[index].write((key, value));
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        // SAFETY: find only returns full slots
+        Some(&unsafe { self.slot(index) }.1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        // SAFETY: find only returns full slots
+        Some(&mut unsafe { self.slots[index].assume_init_mut() }.1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hash_builder.hash_one(key), key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+
+        // lookups already stop at a group with an empty slot in it, so the slot can go straight
+        // back to empty in that case. otherwise it needs a tombstone to keep later keys reachable
+        let group = index / GROUP_WIDTH;
+        if self.group(group).match_empty().any() {
+            self.ctrl[index] = EMPTY;
+        } else {
+            self.ctrl[index] = DELETED;
+            self.tombstones += 1;
+        }
+        self.items -= 1;
+
+        // SAFETY: the slot was full and its control byte no longer says so
+        let (_, value) = unsafe { self.slots[index].assume_init_read() };
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedState;
+
+    // small xorshift generator so the group tests see plenty of control byte patterns
+    fn control_bytes(seed: u64) -> impl Iterator<Item = [u8; GROUP_WIDTH]> {
+        let mut state = seed;
+        std::iter::repeat_with(move || {
+            let mut ctrl = [0; GROUP_WIDTH];
+            for byte in &mut ctrl {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                *byte = match state % 4 {
+                    0 => EMPTY,
+                    1 => DELETED,
+                    _ => (state >> 32) as u8 & 0x7f,
+                };
+            }
+            ctrl
+        })
+    }
+
+    fn expected(ctrl: &[u8; GROUP_WIDTH], f: impl Fn(u8) -> bool) -> u16 {
+        (0..GROUP_WIDTH).filter(|&i| f(ctrl[i])).map(|i| 1 << i).sum()
+    }
+
+    #[test]
+    fn generic_group_matches_scalar() {
+        for ctrl in control_bytes(1).take(2000) {
+            let group = generic::Group::load(&ctrl);
+            for byte in [0, 1, 0x7f, ctrl[0], ctrl[15]] {
+                assert_eq!(group.match_byte(byte).0, expected(&ctrl, |c| c == byte));
+            }
+            assert_eq!(group.match_empty().0, expected(&ctrl, |c| c == EMPTY));
+            assert_eq!(group.match_empty_or_deleted().0, expected(&ctrl, |c| !is_full(c)));
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn sse2_group_matches_scalar() {
+        for ctrl in control_bytes(2).take(2000) {
+            let group = sse2::Group::load(&ctrl);
+            for byte in [0, 1, 0x7f, ctrl[0], ctrl[15]] {
+                assert_eq!(group.match_byte(byte).0, expected(&ctrl, |c| c == byte));
+            }
+            assert_eq!(group.match_empty().0, expected(&ctrl, |c| c == EMPTY));
+            assert_eq!(group.match_empty_or_deleted().0, expected(&ctrl, |c| !is_full(c)));
+        }
+    }
+
+    // CI runs the table tests again with `--cfg swiss_generic`, this makes sure that run really
+    // goes through the portable groups
+    #[cfg(swiss_generic)]
+    #[test]
+    fn table_uses_generic_groups() {
+        assert_eq!(
+            std::any::type_name::<Group>(),
+            std::any::type_name::<generic::Group>()
+        );
+    }
+
+    #[test]
+    fn many_keys_with_churn() {
+        let mut map = SwissHashMap::with_hasher(FixedState::with_seed(9));
+        for i in 0..20_000u32 {
+            map.insert(i, i.to_string());
+        }
+        for i in (0..20_000u32).filter(|i| i % 4 != 0) {
+            assert_eq!(map.remove(&i), Some(i.to_string()));
+        }
+        for i in 20_000..25_000u32 {
+            map.insert(i, i.to_string());
+        }
+
+        assert_eq!(map.len(), 10_000);
+        assert_eq!(map.iter().count(), 10_000);
+        for i in 0..25_000u32 {
+            let present = i % 4 == 0 || i >= 20_000;
+            assert_eq!(map.get(&i).cloned(), present.then(|| i.to_string()));
+        }
+    }
+
+    #[test]
+    fn drops_every_value_once() {
+        use std::rc::Rc;
+
+        let value = Rc::new(());
+        {
+            let mut map = SwissHashMap::new();
+            for i in 0..100 {
+                map.insert(i, Rc::clone(&value));
+            }
+            for i in 0..50 {
+                map.remove(&i);
+            }
+            assert_eq!(Rc::strong_count(&value), 51);
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,205 @@
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::ops::Index;
+
+use crate::{Allocator, DefaultHashBuilder, HashMap};
+
+// cloning copies the buckets as they are, the cloned hasher puts every key in the same place
+impl<K, V, S, A> Clone for HashMap<K, V, S, A>
+where
+    K: Clone,
+    V: Clone,
+    S: Clone,
+    A: Allocator + Clone,
+{
+    fn clone(&self) -> Self {
+        HashMap {
+            buckets: self.buckets.clone(),
+            items: self.items,
+            hash_builder: self.hash_builder.clone(),
+            policy: self.policy,
+        }
+    }
+}
+
+impl<K, V, S, A> fmt::Debug for HashMap<K, V, S, A>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    A: Allocator,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// two maps are equal when they hold the same pairs, whatever buckets those pairs ended up in
+impl<K, V, S, A> PartialEq for HashMap<K, V, S, A>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn eq(&self, other: &HashMap<K, V, S, A>) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key).is_some_and(|v| value == v))
+    }
+}
+
+impl<K, V, S, A> Eq for HashMap<K, V, S, A>
+where
+    K: Hash + Eq,
+    V: Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+}
+
+// panics if the key isn't in the map, use get for a fallible lookup
+impl<K, Q, V, S, A> Index<&Q> for HashMap<K, V, S, A>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in HashMap")
+    }
+}
+
+impl<K, V, S, A> Extend<(K, V)> for HashMap<K, V, S, A>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        // same guess as std: when the map already has items, assume about half the new keys
+        // are duplicates so a stream of repeated keys doesn't balloon the table
+        let hint = iter.size_hint().0;
+        let additional = if self.is_empty() {
+            hint
+        } else {
+            hint.div_ceil(2)
+        };
+        self.reserve(additional);
+
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K, V, S, A> Extend<(&'a K, &'a V)> for HashMap<K, V, S, A>
+where
+    K: Hash + Eq + Copy,
+    V: Copy,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = HashMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, const N: usize> From<[(K, V); N]> for HashMap<K, V, DefaultHashBuilder>
+where
+    K: Hash + Eq,
+{
+    fn from(pairs: [(K, V); N]) -> Self {
+        HashMap::from_iter(pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FixedState, HashMap};
+
+    #[test]
+    fn clone_and_eq() {
+        let map = HashMap::from([("a", 1), ("b", 2)]);
+        let mut copy = map.clone();
+        assert_eq!(map, copy);
+
+        copy.insert("b", 3);
+        assert_ne!(map, copy);
+        copy.insert("b", 2);
+        copy.insert("c", 4);
+        assert_ne!(map, copy);
+    }
+
+    #[test]
+    fn eq_ignores_bucket_layout() {
+        // same pairs, different seeds and table sizes, so the buckets don't line up
+        let mut small = HashMap::with_hasher(FixedState::with_seed(1));
+        let mut large = HashMap::with_capacity_and_hasher(1000, FixedState::with_seed(1));
+        for i in 0..100 {
+            small.insert(i, i * 2);
+            large.insert(99 - i, (99 - i) * 2);
+        }
+        assert_ne!(small.buckets.len(), large.buckets.len());
+        assert_eq!(small, large);
+    }
+
+    #[test]
+    fn debug() {
+        let map = HashMap::from([("a", 1)]);
+        assert_eq!(format!("{map:?}"), r#"{"a": 1}"#);
+
+        let empty: HashMap<u32, u32> = HashMap::default();
+        assert_eq!(format!("{empty:?}"), "{}");
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i * i)).collect();
+        assert_eq!(map.len(), 100);
+        assert_eq!(map[&9], 81);
+
+        map.extend([(1000, 1), (9, 0)]);
+        map.extend([(&2000, &2)]);
+        assert_eq!(map.len(), 102);
+        assert_eq!(map[&9], 0);
+    }
+
+    #[test]
+    fn extend_reserves_from_size_hint() {
+        let mut map = HashMap::new();
+        map.extend((0..1000).map(|i| (i, i)));
+        // one reservation up front instead of doubling up from 10 buckets
+        assert_eq!(map.buckets.len(), 1334);
+    }
+
+    #[test]
+    fn index_with_borrowed_key() {
+        let map = HashMap::from([(String::from("abc"), 1)]);
+        assert_eq!(map["abc"], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found")]
+    fn index_missing_key_panics() {
+        let map = HashMap::from([("abc", 1)]);
+        let _ = map["def"];
+    }
+}